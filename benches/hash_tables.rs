@@ -0,0 +1,268 @@
+// Throughput of every table, and of `std::collections::HashMap` as a baseline, on a few workloads at several
+// sizes and maximum load factors. Each benchmark is warmed up, then timed over a number of samples, and reported
+// as the mean throughput with a 95% confidence interval.
+//
+// Run all of them with `cargo bench`, or only those whose name contains a filter with `cargo bench -- miss`.
+
+use std::collections::HashMap;
+use std::env;
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use alg::bucketized_cuckoo_hash_table::BucketizedCuckooHashTable;
+use alg::cuckoo_hash_table::CuckooHashTable;
+use alg::dhts_hash_table::DoubleHashingTombstoneHashTable;
+use alg::hash_table::{self, HashTable};
+use alg::hopscotch_hash_table::HopscotchHashTable;
+use alg::lprh_hash_table::LinearProbingRobinHoodHashTable;
+use alg::lpts_hash_table::LinearProbingTombstoneHashTable;
+use alg::qpts_hash_table::QuadraticProbingTombstoneHashTable;
+use alg::sc_hash_table::{LinkedListChainingHashTable, SmallVecChainingHashTable};
+use alg::swiss_hash_table::SwissHashTable;
+
+const SIZES: [usize; 2] = [1_000, 100_000];
+const MAX_LOAD_FACTORS: [f64; 2] = [0.5, 0.85];
+// The highest maximum load factor the cuckoo table is benchmarked at.
+const CUCKOO_MAX_LOAD_FACTOR: f64 = 0.5;
+
+const WARM_UP_TIME: Duration = Duration::from_millis(100);
+const SAMPLE_TIME: Duration = Duration::from_millis(20);
+const SAMPLES: usize = 20;
+// Two-sided 95% quantile of Student's t-distribution with `SAMPLES - 1` degrees of freedom.
+const T_95: f64 = 2.093;
+
+// Starting capacity of every table, so that the insert workload pays for growing the table.
+const INITIAL_CAPACITY: usize = 16;
+
+/// The operations the workloads need, so that `HashMap` can run them next to the tables of the crate.
+trait BenchTable {
+    fn insert(&mut self, key: usize, value: usize);
+    fn get(&self, key: &usize) -> Option<&usize>;
+    fn remove(&mut self, key: &usize) -> Option<usize>;
+}
+
+impl<T: HashTable<usize, usize>> BenchTable for T {
+    fn insert(&mut self, key: usize, value: usize) {
+        HashTable::insert(self, key, value);
+    }
+
+    fn get(&self, key: &usize) -> Option<&usize> {
+        HashTable::get(self, key)
+    }
+
+    fn remove(&mut self, key: &usize) -> Option<usize> {
+        HashTable::remove(self, key)
+    }
+}
+
+// The blanket implementation above rules out implementing `BenchTable` for `HashMap` itself, since the crate could
+// implement `HashTable` for it.
+struct StdHashMap(HashMap<usize, usize>);
+
+impl BenchTable for StdHashMap {
+    fn insert(&mut self, key: usize, value: usize) {
+        self.0.insert(key, value);
+    }
+
+    fn get(&self, key: &usize) -> Option<&usize> {
+        self.0.get(key)
+    }
+
+    fn remove(&mut self, key: &usize) -> Option<usize> {
+        self.0.remove(key)
+    }
+}
+
+/// Mean throughput over the samples, in operations per second, and the half width of its 95% confidence
+/// interval.
+struct Throughput {
+    mean: f64,
+    margin: f64,
+}
+
+// Times `routine` on fresh state from `setup`, which isn't timed. `routine` returns the number of operations
+// it ran.
+fn measure<S>(mut setup: impl FnMut() -> S, mut routine: impl FnMut(S) -> usize) -> Throughput {
+    // Warm up, and find out how many runs fill a sample.
+    let mut runs = 0;
+    let mut elapsed = Duration::ZERO;
+    while elapsed < WARM_UP_TIME {
+        let state = setup();
+        let start = Instant::now();
+        black_box(routine(state));
+        elapsed += start.elapsed();
+        runs += 1;
+    }
+    let runs_per_sample = ((SAMPLE_TIME.as_secs_f64() / (elapsed.as_secs_f64() / runs as f64)) as usize).max(1);
+
+    let samples: Vec<f64> = (0..SAMPLES)
+        .map(|_| {
+            let mut operations = 0;
+            let mut elapsed = Duration::ZERO;
+            for _ in 0..runs_per_sample {
+                let state = setup();
+                let start = Instant::now();
+                operations += black_box(routine(state));
+                elapsed += start.elapsed();
+            }
+            operations as f64 / elapsed.as_secs_f64()
+        })
+        .collect();
+
+    let mean = samples.iter().sum::<f64>() / SAMPLES as f64;
+    let variance = samples.iter().map(|sample| (sample - mean).powi(2)).sum::<f64>() / (SAMPLES - 1) as f64;
+    Throughput { mean, margin: T_95 * (variance / SAMPLES as f64).sqrt() }
+}
+
+fn filled<T: BenchTable>(new_table: &impl Fn() -> T, size: usize) -> T {
+    let mut hash_table = new_table();
+    for key in 0..size {
+        hash_table.insert(key, key);
+    }
+    hash_table
+}
+
+// Runs every workload against the tables built by `new_table`, and prints the ones matching `filter`.
+fn bench_table<T: BenchTable>(
+    name: &str,
+    size: usize,
+    max_load_factor: f64,
+    filter: &str,
+    new_table: impl Fn() -> T,
+) {
+    let report = |workload: &str, throughput: Throughput| {
+        println!(
+            "{:<13} size {:>7}  max load {:.2}  {:<22} {:>8.2} Mops/s ± {:>5.2}",
+            workload,
+            size,
+            max_load_factor,
+            name,
+            throughput.mean / 1e6,
+            throughput.margin / 1e6,
+        );
+    };
+    let matches = |workload: &str| format!("{}/{}", workload, name).contains(filter);
+
+    if matches("insert") {
+        // Grows the table from its initial capacity, through every resize on the way.
+        report(
+            "insert",
+            measure(&new_table, |mut hash_table| {
+                for key in 0..size {
+                    hash_table.insert(key, key);
+                }
+                size
+            }),
+        );
+    }
+    if matches("lookup hit") {
+        let hash_table = filled(&new_table, size);
+        report(
+            "lookup hit",
+            measure(
+                || (),
+                |()| {
+                    for key in 0..size {
+                        black_box(hash_table.get(&key));
+                    }
+                    size
+                },
+            ),
+        );
+    }
+    if matches("lookup miss") {
+        let hash_table = filled(&new_table, size);
+        report(
+            "lookup miss",
+            measure(
+                || (),
+                |()| {
+                    for key in size..2 * size {
+                        black_box(hash_table.get(&key));
+                    }
+                    size
+                },
+            ),
+        );
+    }
+    if matches("delete churn") {
+        // Replaces every key with a new one, keeping the size steady while tombstones pile up.
+        report(
+            "delete churn",
+            measure(
+                || filled(&new_table, size),
+                |mut hash_table| {
+                    for key in 0..size {
+                        hash_table.remove(&key);
+                        hash_table.insert(key + size, key);
+                    }
+                    2 * size
+                },
+            ),
+        );
+    }
+    if matches("mixed") {
+        // Half lookups, three tenths insertions and a fifth removals, over twice as many keys as the table holds.
+        report(
+            "mixed",
+            measure(
+                || filled(&new_table, size),
+                |mut hash_table| {
+                    for i in 0..size as u64 {
+                        let random = hash_table::mix64(i);
+                        let key = (random >> 8) as usize % (2 * size);
+                        match random % 10 {
+                            0..5 => {
+                                black_box(hash_table.get(&key));
+                            }
+                            5..8 => hash_table.insert(key, key),
+                            _ => {
+                                black_box(hash_table.remove(&key));
+                            }
+                        }
+                    }
+                    size
+                },
+            ),
+        );
+    }
+}
+
+// Builds a table of the given type with the default hasher, and the maximum load factor being benchmarked.
+macro_rules! bench_tables {
+    ($size:expr, $max_load_factor:expr, $filter:expr, [$($name:expr => $table:expr),* $(,)?]) => {{
+        $(
+            bench_table($name, $size, $max_load_factor, $filter, || {
+                let mut hash_table = $table;
+                hash_table.set_max_load_factor($max_load_factor);
+                hash_table
+            });
+        )*
+    }};
+}
+
+fn main() {
+    // `cargo bench` passes `--bench`, and anything else is a filter on the benchmark names.
+    let filter = env::args().skip(1).find(|arg| !arg.starts_with("--")).unwrap_or_default();
+    for size in SIZES {
+        for max_load_factor in MAX_LOAD_FACTORS {
+            // `HashMap` picks its own load factor, so it is the same baseline at every maximum load factor.
+            bench_table("std HashMap", size, max_load_factor, &filter, || StdHashMap(HashMap::new()));
+            bench_tables!(size, max_load_factor, &filter, [
+                "lpts" => LinearProbingTombstoneHashTable::new(INITIAL_CAPACITY),
+                "qpts" => QuadraticProbingTombstoneHashTable::new(INITIAL_CAPACITY),
+                "dhts" => DoubleHashingTombstoneHashTable::new(INITIAL_CAPACITY),
+                "lprh" => LinearProbingRobinHoodHashTable::new(INITIAL_CAPACITY),
+                "bucketized cuckoo" => BucketizedCuckooHashTable::new(INITIAL_CAPACITY),
+                "hopscotch" => HopscotchHashTable::new(INITIAL_CAPACITY),
+                "linked list chaining" => LinkedListChainingHashTable::new(INITIAL_CAPACITY),
+                "small vector chaining" => SmallVecChainingHashTable::new(INITIAL_CAPACITY),
+                "swiss" => SwissHashTable::new(INITIAL_CAPACITY),
+            ]);
+            // Two choices per key stop finding room for new keys long before 0.85, and every insertion rehashes.
+            if max_load_factor <= CUCKOO_MAX_LOAD_FACTOR {
+                bench_tables!(size, max_load_factor, &filter, ["cuckoo" => CuckooHashTable::new(INITIAL_CAPACITY)]);
+            }
+        }
+    }
+}
@@ -1,63 +1,324 @@
-use bitvec;
+use std::collections::hash_map::RandomState;
+use std::f64::consts::LN_2;
+use std::hash::{BuildHasher, Hash};
+use std::iter;
+use std::marker::PhantomData;
+use std::ops::Range;
 
-struct BloomFilter {
+use crate::hash_table::{self, DeterministicState};
+
+/// Set membership in a fixed number of bits: `contains` can return false positives, but never false negatives.
+/// Every item sets `hash_count` bits, at positions derived from a single hash of the item, so items of any
+/// `Hash` type can go in, unsized ones like `str` and `[u8]` included.
+pub struct BloomFilter<T: ?Sized, S = RandomState> {
     filter: bitvec::vec::BitVec,
     capacity: usize,
+    hash_count: usize,
+    hash_builder: S,
+    // The filter only ever hashes borrowed items, and never holds one.
+    marker: PhantomData<fn(&T)>,
 }
 
-impl BloomFilter {
-    fn new(capacity: usize) -> Self {
+impl<T: Hash + ?Sized> BloomFilter<T> {
+    pub fn new(capacity: usize, hash_count: usize) -> Self {
+        Self::with_hasher(capacity, hash_count, RandomState::new())
+    }
+
+    /// A filter with the fewest bits, and the best number of hash functions for them, that gives false positives
+    /// at `false_positive_rate` once `expected_items` items are in.
+    pub fn with_rate(expected_items: usize, false_positive_rate: f64) -> Self {
+        Self::with_rate_and_hasher(expected_items, false_positive_rate, RandomState::new())
+    }
+}
+
+impl<T: Hash + ?Sized, S: BuildHasher> BloomFilter<T, S> {
+    pub fn with_hasher(capacity: usize, hash_count: usize, hash_builder: S) -> Self {
+        assert!(capacity > 0, "A Bloom filter needs at least one bit");
+        assert!(hash_count > 0, "A Bloom filter needs at least one hash function");
         BloomFilter {
             filter: bitvec::vec::BitVec::repeat(false, capacity),
-            capacity
+            capacity,
+            hash_count,
+            hash_builder,
+            marker: PhantomData,
         }
     }
 
-    fn get_hashes(&self, item: usize) -> Vec<usize> {
-        return vec![item % self.capacity, (item + 10) % self.capacity];
+    pub fn with_rate_and_hasher(expected_items: usize, false_positive_rate: f64, hash_builder: S) -> Self {
+        let (capacity, hash_count) = optimal_size(expected_items, false_positive_rate);
+        Self::with_hasher(capacity, hash_count, hash_builder)
     }
 
-    fn insert(&mut self, item: usize) {
-        for hash in self.get_hashes(item) {
-            self.filter.set(hash, true);
-        }
+    fn positions(&self, item: &T) -> impl Iterator<Item = usize> + use<T, S> {
+        double_hashing_positions(self.hash_builder.hash_one(item), self.capacity, self.hash_count)
     }
 
-    fn contains(&self, item: usize) -> bool {
-        for hash in self.get_hashes(item) {
-            let bitval_opt = self.filter.get(hash);
-            if bitval_opt.is_none() || bitval_opt.unwrap() == false {
-                return false;
-            }
+    pub fn insert(&mut self, item: &T) {
+        for position in self.positions(item) {
+            self.filter.set(position, true);
         }
-        return true;
     }
+
+    pub fn contains(&self, item: &T) -> bool {
+        self.positions(item).all(|position| self.filter[position])
+    }
+
+    /// Chance that `contains` returns true for an item that was never inserted, given how many bits are set.
+    pub fn estimated_fp_rate(&self) -> f64 {
+        estimated_fp_rate(self.filter.count_ones(), self.capacity, self.hash_count)
+    }
+
+    /// Number of distinct items inserted, estimated from how many bits are set. Infinite once every bit is set.
+    pub fn estimated_len(&self) -> f64 {
+        estimated_len(self.filter.count_ones(), self.capacity, self.hash_count)
+    }
+}
+
+// Kirsch–Mitzenmacher double hashing: the positions `h1 + i * h2` for `i` in `0..hash_count` are as good as
+// `hash_count` independent hash functions for a Bloom filter, and need only one hash of the item. `h2` is mixed
+// out of the same hash, like the probe step of the double hashing table. A step coprime with the capacity visits
+// every position before coming back to the first one, so the positions are distinct whenever `hash_count` is at
+// most the capacity. Most steps already are, and the rest are a few increments away from one that is.
+pub(crate) fn double_hashing_positions(hash: u64, capacity: usize, hash_count: usize) -> impl Iterator<Item = usize> {
+    let capacity = capacity as u64;
+    let mut step = hash_table::mix64(hash) % capacity;
+    while gcd(step, capacity) != 1 {
+        step = (step + 1) % capacity;
+    }
+    iter::successors(Some(hash % capacity), move |position| Some((position + step) % capacity))
+        .take(hash_count)
+        .map(|position| position as usize)
+}
+
+fn gcd(mut a: u64, mut b: u64) -> u64 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+// Number of positions and hash functions that give false positives at `false_positive_rate` with `expected_items`
+// items in: m = -n ln p / (ln 2)^2 positions, and k = (m / n) ln 2 hash functions.
+pub(crate) fn optimal_size(expected_items: usize, false_positive_rate: f64) -> (usize, usize) {
+    assert!(
+        false_positive_rate > 0.0 && false_positive_rate < 1.0,
+        "The false positive rate must be between 0 and 1, exclusive",
+    );
+    let expected_items = expected_items.max(1) as f64;
+    let capacity = (-expected_items * false_positive_rate.ln() / (LN_2 * LN_2)).ceil();
+    let hash_count = (capacity / expected_items * LN_2).round().max(1.0);
+    (capacity as usize, hash_count as usize)
+}
+
+// Chance that all `hash_count` positions of an item not inserted are among the `set` ones.
+pub(crate) fn estimated_fp_rate(set: usize, capacity: usize, hash_count: usize) -> f64 {
+    (set as f64 / capacity as f64).powi(hash_count as i32)
+}
+
+// Swamidass and Baldi's estimate of the number of distinct items, -(m / k) ln(1 - X / m) with X positions set.
+pub(crate) fn estimated_len(set: usize, capacity: usize, hash_count: usize) -> f64 {
+    -(capacity as f64 / hash_count as f64) * (1.0 - set as f64 / capacity as f64).ln()
+}
+
+// Fraction of `queries`, none of which were inserted, that the filter claims to contain.
+fn false_positive_rate<S: BuildHasher>(bloom_filter: &BloomFilter<usize, S>, queries: Range<usize>) -> f64 {
+    let len = queries.len();
+    queries.filter(|item| bloom_filter.contains(item)).count() as f64 / len as f64
 }
 
 pub fn run() {
-    let mut bloom_filter = BloomFilter::new(100);
-    bloom_filter.insert(1);
-    bloom_filter.insert(2);
-    bloom_filter.insert(3);
-    bloom_filter.insert(4);
-    bloom_filter.insert(5);
-    bloom_filter.insert(6);
-    bloom_filter.insert(15);
-    bloom_filter.insert(16);
-    bloom_filter.insert(35);
-    bloom_filter.insert(36);
-    assert!(bloom_filter.contains(1));
-    assert!(bloom_filter.contains(2));
-    assert!(bloom_filter.contains(3));
-    assert!(bloom_filter.contains(4));
-    assert!(bloom_filter.contains(5));
-    assert!(bloom_filter.contains(6));
-    assert!(bloom_filter.contains(15));
-    assert!(bloom_filter.contains(16));
-    assert!(bloom_filter.contains(35));
-    assert!(bloom_filter.contains(36));
-    // False positives are expected
-    assert!(bloom_filter.contains(25));
-    assert!(bloom_filter.contains(26));
+    let mut bloom_filter = BloomFilter::with_hasher(100, 3, DeterministicState::default());
+    let items = [1, 2, 3, 4, 5, 6, 15, 16, 35, 36];
+    for item in &items {
+        bloom_filter.insert(item);
+    }
+    for item in &items {
+        assert!(bloom_filter.contains(item));
+    }
+    // Ten items in a hundred bits with three hash functions give false positives about 1.7% of the time on
+    // average. With so few bits, the rate of any one filter depends on how many of them its items happened to set.
+    let rate = false_positive_rate(&bloom_filter, 1000..101_000);
+    println!(
+        "False positive rate: {:.2}%, estimated from the bits set: {:.2}%",
+        rate * 100.0,
+        bloom_filter.estimated_fp_rate() * 100.0,
+    );
+    assert!(rate < 0.04);
+    assert!((rate - bloom_filter.estimated_fp_rate()).abs() < 0.01);
+    // Items a multiple of the capacity apart used to set exactly the same bits, so these were all false positives.
+    let false_positives = (1..1000).filter(|multiple| bloom_filter.contains(&(multiple * 100 + 1))).count();
+    println!("{} of 999 items a multiple of the capacity away from 1 are false positives", false_positives);
+    assert!(false_positives < 100);
+
+    // Sized for a thousand items at 1%, which takes 9586 bits and seven hash functions.
+    let mut bloom_filter = BloomFilter::with_rate_and_hasher(1000, 0.01, DeterministicState::default());
+    assert!(bloom_filter.capacity == 9586 && bloom_filter.hash_count == 7);
+    for item in 0..1000 {
+        bloom_filter.insert(&item);
+    }
+    let rate = false_positive_rate(&bloom_filter, 1000..101_000);
+    println!(
+        "Sized for 1%: false positive rate {:.2}%, estimated {:.2}%, with an estimated {:.0} items",
+        rate * 100.0,
+        bloom_filter.estimated_fp_rate() * 100.0,
+        bloom_filter.estimated_len(),
+    );
+    assert!(rate < 0.012);
+    assert!((bloom_filter.estimated_fp_rate() - 0.01).abs() < 0.001);
+    assert!((bloom_filter.estimated_len() - 1000.0).abs() < 20.0);
+
+    // Unsized items go in by reference, and owned ones find them through `Deref`.
+    let mut words: BloomFilter<str, _> = BloomFilter::with_rate_and_hasher(100, 0.01, DeterministicState::default());
+    for word in ["apple", "banana", "cherry"] {
+        words.insert(word);
+    }
+    assert!(words.contains("banana") && words.contains(&String::from("cherry")));
+    assert!(!words.contains("durian"));
+    let mut bytes: BloomFilter<[u8], _> = BloomFilter::with_rate_and_hasher(100, 0.01, DeterministicState::default());
+    bytes.insert(b"\x00\x01\x02");
+    assert!(bytes.contains(&Vec::from([0, 1, 2])));
+    assert!(!bytes.contains(&[0, 1]));
     println!("All tests passed");
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CAPACITY: usize = 10_000;
+    const ITEMS: usize = 1000;
+
+    fn filled(hash_count: usize) -> BloomFilter<usize, DeterministicState> {
+        let mut bloom_filter = BloomFilter::with_hasher(CAPACITY, hash_count, DeterministicState::default());
+        for item in 0..ITEMS {
+            bloom_filter.insert(&item);
+        }
+        bloom_filter
+    }
+
+    // (1 - e^(-kn/m))^k, the false positive rate of a filter with independent hash functions.
+    fn expected_false_positive_rate(hash_count: usize) -> f64 {
+        let hash_count = hash_count as f64;
+        (1.0 - (-hash_count * ITEMS as f64 / CAPACITY as f64).exp()).powf(hash_count)
+    }
+
+    #[test]
+    fn contains_every_inserted_item() {
+        for hash_count in [1, 2, 4, 7] {
+            let bloom_filter = filled(hash_count);
+            assert!((0..ITEMS).all(|item| bloom_filter.contains(&item)));
+        }
+    }
+
+    #[test]
+    fn false_positive_rate_matches_independent_hash_functions() {
+        let mut rates = Vec::new();
+        for hash_count in 1..=10 {
+            let rate = false_positive_rate(&filled(hash_count), ITEMS..ITEMS + 200_000);
+            let expected = expected_false_positive_rate(hash_count);
+            assert!(
+                (rate - expected).abs() < expected * 0.2,
+                "{} hash functions gave a false positive rate of {}, expected {}",
+                hash_count,
+                rate,
+                expected,
+            );
+            rates.push(rate);
+        }
+        // Ten bits per item are best used by about seven hash functions.
+        let best = (1..=10).min_by(|&a, &b| rates[a - 1].total_cmp(&rates[b - 1])).unwrap();
+        assert!((6..=8).contains(&best), "{} hash functions gave the lowest false positive rate", best);
+    }
+
+    #[test]
+    fn items_a_multiple_of_the_capacity_apart_are_independent() {
+        let mut bloom_filter = BloomFilter::with_hasher(CAPACITY, 4, DeterministicState::default());
+        bloom_filter.insert(&7);
+        let false_positives = (1..10_000).filter(|multiple| bloom_filter.contains(&(multiple * CAPACITY + 7))).count();
+        assert!(false_positives == 0, "{} false positives", false_positives);
+    }
+
+    #[test]
+    fn with_rate_meets_the_false_positive_rate() {
+        for (expected_items, target) in [(100, 0.1), (1000, 0.05), (5000, 0.01), (2000, 0.001)] {
+            let mut bloom_filter =
+                BloomFilter::with_rate_and_hasher(expected_items, target, DeterministicState::default());
+            for item in 0..expected_items {
+                bloom_filter.insert(&item);
+            }
+            let rate = false_positive_rate(&bloom_filter, expected_items..expected_items + 200_000);
+            assert!(
+                rate < target * 1.2,
+                "Sized for {} items at {}, but gave false positives at {}",
+                expected_items,
+                target,
+                rate,
+            );
+            assert!((bloom_filter.estimated_fp_rate() - target).abs() < target * 0.2);
+        }
+    }
+
+    #[test]
+    fn estimated_len_tracks_the_items_inserted() {
+        let mut bloom_filter = BloomFilter::with_hasher(CAPACITY, 4, DeterministicState::default());
+        assert!(bloom_filter.estimated_len() == 0.0);
+        assert!(bloom_filter.estimated_fp_rate() == 0.0);
+        for len in 1..=2000 {
+            bloom_filter.insert(&len);
+            // Inserting an item again changes nothing.
+            bloom_filter.insert(&len);
+            if len % 250 == 0 {
+                let estimate = bloom_filter.estimated_len();
+                assert!((estimate - len as f64).abs() < len as f64 * 0.05, "Estimated {} items, not {}", estimate, len);
+            }
+        }
+    }
+
+    #[test]
+    fn hashes_borrowed_and_owned_items_alike() {
+        #[derive(Hash)]
+        struct Point {
+            x: i32,
+            y: i32,
+        }
+
+        let mut points = BloomFilter::with_hasher(CAPACITY, 4, DeterministicState::default());
+        let mut names: BloomFilter<str, _> = BloomFilter::with_hasher(CAPACITY, 4, DeterministicState::default());
+        let mut bytes: BloomFilter<[u8], _> = BloomFilter::with_hasher(CAPACITY, 4, DeterministicState::default());
+        for i in 0..ITEMS as i32 {
+            points.insert(&Point { x: i, y: -i });
+            names.insert(&format!("name {}", i));
+            bytes.insert(&i.to_le_bytes());
+        }
+        for i in 0..ITEMS as i32 {
+            assert!(points.contains(&Point { x: i, y: -i }));
+            assert!(names.contains(&format!("name {}", i)));
+            assert!(bytes.contains(&i.to_le_bytes()));
+        }
+        let false_positives = (ITEMS as i32..2 * ITEMS as i32)
+            .filter(|&i| points.contains(&Point { x: i, y: -i }) || names.contains(&format!("name {}", i)))
+            .count();
+        assert!(false_positives < ITEMS / 20);
+    }
+
+    #[test]
+    fn positions_are_distinct() {
+        // Prime, power of two and highly composite capacities, where many steps share a factor with the capacity.
+        for capacity in [1, 2, 7, 12, 64, 100, 360, 9586] {
+            for hash_count in [1, 2, 7, 12].into_iter().filter(|&hash_count| hash_count <= capacity) {
+                for hash in (0..1000).map(hash_table::mix64) {
+                    let mut positions: Vec<usize> = double_hashing_positions(hash, capacity, hash_count).collect();
+                    positions.sort_unstable();
+                    positions.dedup();
+                    assert!(
+                        positions.len() == hash_count,
+                        "Only {} distinct positions out of {} in {}",
+                        positions.len(),
+                        hash_count,
+                        capacity,
+                    );
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,637 @@
+use std::collections::hash_map::RandomState;
+use std::collections::HashSet;
+use std::fmt::Debug;
+use std::hash::{BuildHasher, Hash};
+use std::ops::Range;
+
+use crate::hash_table::{
+    self, BuildIdentityHasher, DeterministicState, Entry, HashTable, HashTableStats, InsertAtBucket, MigrateBuckets,
+    OccupiedEntry, Stats, VacantEntry,
+};
+
+const DEFAULT_SLOTS_PER_BUCKET: usize = 4;
+
+// Longest eviction path the breadth-first search looks for. The number of slots it visits grows
+// exponentially with the path length, so short paths keep failed insertions cheap.
+const DEFAULT_MAX_DISPLACEMENTS: usize = 5;
+
+// Number of entries that can overflow into the stash before the table has to be rehashed.
+const STASH_SIZE: usize = 4;
+
+// Number of times the table is rehashed with fresh seeds at the same capacity before giving up and growing.
+const MAX_REHASHES_BEFORE_GROWING: usize = 3;
+
+// With two hash functions and four slots per bucket, insertions only start failing at around 95% full.
+const DEFAULT_MAX_LOAD_FACTOR: f64 = 0.9;
+
+/// Cuckoo hashing where each of a key's two buckets holds several entries. Insertions that find both
+/// buckets full search breadth-first for the shortest chain of evictions that frees a slot, and the few
+/// keys that still can't be placed go to a small stash that every lookup also checks.
+pub struct BucketizedCuckooHashTable<K, V, S = RandomState> {
+    // The slots of bucket b are `b * slots_per_bucket..(b + 1) * slots_per_bucket`, followed by the
+    // `STASH_SIZE` slots of the stash after the last bucket.
+    table: Vec<Option<(K, V)>>,
+    // Number of slots, not counting the stash.
+    capacity: usize,
+    size: usize,
+    hash_builder: S,
+    max_load_factor: f64,
+    growth_factor: f64,
+    // When set, removals shrink the table once the load factor drops below this value.
+    min_load_factor: Option<f64>,
+    primary_seed: u64,
+    secondary_seed: u64,
+    slots_per_bucket: usize,
+    max_displacements: usize,
+    rehash_count: usize,
+    max_achievable_load_factor: Option<f64>,
+    // Number of placements, including the ones made while rehashing, that displaced each number of entries.
+    displacements: Vec<usize>,
+}
+
+impl<K: Hash + Eq, V> BucketizedCuckooHashTable<K, V> {
+    pub fn new(capacity: usize) -> Self {
+        Self::with_hasher(capacity, RandomState::new())
+    }
+}
+
+impl<K: Hash + Eq, V, S: BuildHasher> BucketizedCuckooHashTable<K, V, S> {
+    /// Creates a table with room for at least `capacity` entries, rounded up to whole buckets.
+    pub fn with_hasher(capacity: usize, hash_builder: S) -> Self {
+        let capacity = capacity.div_ceil(DEFAULT_SLOTS_PER_BUCKET).max(1) * DEFAULT_SLOTS_PER_BUCKET;
+        BucketizedCuckooHashTable {
+            table: hash_table::empty_buckets(capacity + STASH_SIZE),
+            capacity,
+            size: 0,
+            hash_builder,
+            max_load_factor: DEFAULT_MAX_LOAD_FACTOR,
+            growth_factor: hash_table::DEFAULT_GROWTH_FACTOR,
+            min_load_factor: None,
+            primary_seed: hash_table::mix64(0),
+            secondary_seed: hash_table::mix64(1),
+            slots_per_bucket: DEFAULT_SLOTS_PER_BUCKET,
+            max_displacements: DEFAULT_MAX_DISPLACEMENTS,
+            rehash_count: 0,
+            max_achievable_load_factor: None,
+            displacements: Vec::new(),
+        }
+    }
+
+    /// The table grows before an insertion would take it above this fraction of its capacity.
+    pub fn set_max_load_factor(&mut self, max_load_factor: f64) {
+        hash_table::assert_valid_max_load_factor(max_load_factor);
+        hash_table::assert_valid_min_load_factor(self.min_load_factor, max_load_factor);
+        self.max_load_factor = max_load_factor;
+    }
+
+    /// Makes removals shrink the table once its load factor drops below `min_load_factor`.
+    /// `None`, the default, never shrinks automatically.
+    pub fn set_min_load_factor(&mut self, min_load_factor: Option<f64>) {
+        hash_table::assert_valid_min_load_factor(min_load_factor, self.max_load_factor);
+        self.min_load_factor = min_load_factor;
+    }
+
+    /// Factor the capacity is multiplied by whenever the table grows.
+    pub fn set_growth_factor(&mut self, growth_factor: f64) {
+        hash_table::assert_valid_growth_factor(growth_factor);
+        self.growth_factor = growth_factor;
+    }
+
+    /// Number of entries each bucket holds. More slots let the table fill up further before insertions fail,
+    /// at the cost of comparing more keys per lookup. Rebuilds the table, rounding its capacity up to whole
+    /// buckets.
+    pub fn set_slots_per_bucket(&mut self, slots_per_bucket: usize) {
+        assert!(slots_per_bucket > 0, "Slots per bucket must be positive, got {}", slots_per_bucket);
+        self.slots_per_bucket = slots_per_bucket;
+        assert!(self.resize_to(self.capacity));
+    }
+
+    /// Maximum number of entries moved by a single insertion before the entry goes to the stash.
+    pub fn set_max_displacements(&mut self, max_displacements: usize) {
+        self.max_displacements = max_displacements;
+    }
+
+    /// Number of times the table was rebuilt with new seeds because an insertion could not be placed.
+    pub fn rehash_count(&self) -> usize {
+        self.rehash_count
+    }
+
+    /// Number of entries currently in the stash.
+    pub fn stash_len(&self) -> usize {
+        self.table[self.stash_slots()].iter().flatten().count()
+    }
+
+    /// Highest load factor at which an insertion found neither a free slot, an eviction path nor room in the
+    /// stash, and forced a rehash. `None` while no insertion has failed. With the max load factor set to 1,
+    /// this is how full the table gets in practice.
+    pub fn max_achievable_load_factor(&self) -> Option<f64> {
+        self.max_achievable_load_factor
+    }
+
+    fn number_of_buckets(&self) -> usize {
+        self.capacity / self.slots_per_bucket
+    }
+
+    fn get_bucket(&self, key: &K, seed: u64) -> usize {
+        (hash_table::mix64(self.hash_builder.hash_one(key) ^ seed) % self.number_of_buckets() as u64) as usize
+    }
+
+    fn get_primary_bucket(&self, key: &K) -> usize {
+        self.get_bucket(key, self.primary_seed)
+    }
+
+    fn get_secondary_bucket(&self, key: &K) -> usize {
+        self.get_bucket(key, self.secondary_seed)
+    }
+
+    // The other bucket of the key stored in a slot of `bucket`.
+    fn get_alternate_bucket(&self, key: &K, bucket: usize) -> usize {
+        let primary_bucket = self.get_primary_bucket(key);
+        if bucket == primary_bucket {
+            self.get_secondary_bucket(key)
+        } else {
+            primary_bucket
+        }
+    }
+
+    fn slots(&self, bucket: usize) -> Range<usize> {
+        bucket * self.slots_per_bucket..(bucket + 1) * self.slots_per_bucket
+    }
+
+    fn stash_slots(&self) -> Range<usize> {
+        self.capacity..self.capacity + STASH_SIZE
+    }
+
+    fn find_empty_slot(&self, slots: Range<usize>) -> Option<usize> {
+        slots.into_iter().find(|&slot| self.table[slot].is_none())
+    }
+
+    fn reseed(&mut self) {
+        self.primary_seed = hash_table::mix64(self.secondary_seed);
+        self.secondary_seed = hash_table::mix64(self.primary_seed);
+        self.rehash_count += 1;
+    }
+
+    fn resize_to(&mut self, new_capacity: usize) -> bool {
+        let new_capacity = new_capacity.div_ceil(self.slots_per_bucket).max(1) * self.slots_per_bucket;
+        if !hash_table::fits_in_capacity(self.size, new_capacity, self.max_load_factor) {
+            return false;
+        }
+        self.capacity = new_capacity;
+        let old_table = std::mem::replace(&mut self.table, hash_table::empty_buckets(self.capacity + STASH_SIZE));
+        self.size = 0;
+        for (key, value) in old_table.into_iter().flatten() {
+            self.insert_new_key(key, value);
+        }
+        true
+    }
+
+    // Shrinks the table if removals took it below the minimum load factor.
+    fn shrink_if_underloaded(&mut self) {
+        if let Some(min_load_factor) = self.min_load_factor
+            && (self.size as f64) < self.capacity as f64 * min_load_factor
+        {
+            self.shrink_to(hash_table::shrunk_capacity(self.size, min_load_factor, self.max_load_factor));
+        }
+    }
+
+    fn resize(&mut self) {
+        assert!(self.resize_to(hash_table::grown_capacity(self.capacity, self.growth_factor)));
+    }
+
+    // Searches breadth-first for the shortest chain of evictions that frees a slot in `primary_bucket` or
+    // `secondary_bucket`, both of which are full. Returns the slots along the chain: it starts in one of the
+    // two buckets, each next slot is in the other bucket of the previous slot's occupant, and the last slot
+    // is empty. Returns None if no chain of at most `max_displacements` moves exists.
+    fn find_eviction_path(&self, primary_bucket: usize, secondary_bucket: usize) -> Option<Vec<usize>> {
+        // Every slot in the queue remembers the position in the queue of the slot whose occupant it would
+        // evict, and how many moves it takes to free it.
+        let mut queue: Vec<(usize, Option<usize>, usize)> = Vec::new();
+        let mut visited_buckets = HashSet::new();
+        for bucket in [primary_bucket, secondary_bucket] {
+            if visited_buckets.insert(bucket) {
+                queue.extend(self.slots(bucket).map(|slot| (slot, None, 1)));
+            }
+        }
+
+        let mut next = 0;
+        while let Some(&(slot, _, moves)) = queue.get(next) {
+            if moves > self.max_displacements {
+                return None;
+            }
+            let (occupant_key, _) = self.table[slot].as_ref().unwrap();
+            let alternate_bucket = self.get_alternate_bucket(occupant_key, slot / self.slots_per_bucket);
+            if let Some(empty_slot) = self.find_empty_slot(self.slots(alternate_bucket)) {
+                let mut path = vec![empty_slot];
+                let mut position = Some(next);
+                while let Some(current) = position {
+                    path.push(queue[current].0);
+                    position = queue[current].1;
+                }
+                path.reverse();
+                return Some(path);
+            }
+            if visited_buckets.insert(alternate_bucket) {
+                queue.extend(self.slots(alternate_bucket).map(|slot| (slot, Some(next), moves + 1)));
+            }
+            next += 1;
+        }
+        None
+    }
+
+    // Places a key known not to be in the table and returns the slot it ended up in.
+    fn insert_new_key(&mut self, key: K, value: V) -> usize {
+        let mut rehashes = 0;
+        loop {
+            let primary_bucket = self.get_primary_bucket(&key);
+            let secondary_bucket = self.get_secondary_bucket(&key);
+            let path = self
+                .find_empty_slot(self.slots(primary_bucket))
+                .or_else(|| self.find_empty_slot(self.slots(secondary_bucket)))
+                .map(|slot| vec![slot])
+                .or_else(|| self.find_eviction_path(primary_bucket, secondary_bucket))
+                .or_else(|| self.find_empty_slot(self.stash_slots()).map(|slot| vec![slot]));
+
+            if let Some(path) = path {
+                // Shift every entry on the path one step along, starting from the empty end, then put the
+                // new entry in the slot that was freed at the front.
+                for i in (1..path.len()).rev() {
+                    self.table[path[i]] = self.table[path[i - 1]].take();
+                }
+                self.table[path[0]] = Some((key, value));
+                self.size += 1;
+                hash_table::add_to_histogram(&mut self.displacements, path.len() - 1);
+                return path[0];
+            }
+
+            let load_factor = self.size as f64 / self.capacity as f64;
+            self.max_achievable_load_factor = Some(self.max_achievable_load_factor.unwrap_or(0.0).max(load_factor));
+            // Try new seeds a few times before paying for a bigger table.
+            if rehashes < MAX_REHASHES_BEFORE_GROWING {
+                rehashes += 1;
+                self.reseed();
+                assert!(self.resize_to(self.capacity));
+            } else {
+                rehashes = 0;
+                self.resize();
+            }
+        }
+    }
+
+    // Moves stashed entries back into their buckets once removals have made room for them.
+    fn move_stash_into_buckets(&mut self) {
+        for stash_slot in self.stash_slots() {
+            let Some((key, _)) = &self.table[stash_slot] else {
+                continue;
+            };
+            let primary_slots = self.slots(self.get_primary_bucket(key));
+            let secondary_slots = self.slots(self.get_secondary_bucket(key));
+            if let Some(slot) = self.find_empty_slot(primary_slots).or_else(|| self.find_empty_slot(secondary_slots)) {
+                self.table[slot] = self.table[stash_slot].take();
+            }
+        }
+    }
+
+    // Slots a lookup for `key` checks, in order.
+    fn lookup_slots(&self, key: &K) -> impl Iterator<Item = usize> + use<K, V, S> {
+        let primary_slots = self.slots(self.get_primary_bucket(key));
+        let secondary_slots = self.slots(self.get_secondary_bucket(key));
+        primary_slots.chain(secondary_slots).chain(self.stash_slots())
+    }
+
+    fn find_index_for_key_if_exists(&self, key: &K) -> Option<usize> {
+        self.lookup_slots(key)
+            .find(|&slot| matches!(&self.table[slot], Some((existing_key, _)) if existing_key == key))
+    }
+}
+
+impl<K: Debug, V: Debug, S> BucketizedCuckooHashTable<K, V, S> {
+    pub fn print(&self) {
+        println!("Capacity: {}", self.capacity);
+        println!("Size: {}", self.size);
+        println!("Buckets:");
+        let format_slots = |slots: &[Option<(K, V)>]| -> String {
+            let slots: Vec<String> = slots
+                .iter()
+                .map(|entry| match entry {
+                    Some((key, value)) => format!("{:?}: {:?}", key, value),
+                    None => "<None>".to_string(),
+                })
+                .collect();
+            slots.join(", ")
+        };
+        let (buckets, stash) = self.table.split_at(self.capacity);
+        for bucket in buckets.chunks(self.slots_per_bucket) {
+            println!("\t[{}]", format_slots(bucket));
+        }
+        println!("Stash:");
+        println!("\t[{}]", format_slots(stash));
+    }
+}
+
+impl<K: Hash + Eq, V, S: BuildHasher> HashTable<K, V> for BucketizedCuckooHashTable<K, V, S> {
+    fn len(&self) -> usize {
+        self.size
+    }
+
+    fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    fn entry(&mut self, key: K) -> Entry<'_, K, V> {
+        match self.find_index_for_key_if_exists(&key) {
+            Some(index) => Entry::Occupied(OccupiedEntry::from_bucket(&mut self.table[index])),
+            // Cuckoo insertion picks the slot itself, since it may have to evict entries or grow first.
+            None => Entry::Vacant(VacantEntry::new(key, 0, self)),
+        }
+    }
+
+    fn get_key_value(&self, key: &K) -> Option<(&K, &V)> {
+        let found_index = self.find_index_for_key_if_exists(key)?;
+        self.table[found_index].as_ref().map(|(key, value)| (key, value))
+    }
+
+    fn get_mut(&mut self, key: &K) -> Option<&mut V> {
+        let found_index = self.find_index_for_key_if_exists(key)?;
+        self.table[found_index].as_mut().map(|(_key, value)| value)
+    }
+
+    fn get_many_mut<const N: usize>(&mut self, keys: [&K; N]) -> Option<[&mut V; N]> {
+        let mut indices = [0; N];
+        for (index, key) in indices.iter_mut().zip(keys) {
+            *index = self.find_index_for_key_if_exists(key)?;
+        }
+        hash_table::values_at_disjoint_buckets(&mut self.table, indices)
+    }
+
+    fn remove_entry(&mut self, key: &K) -> Option<(K, V)> {
+        let found_index = self.find_index_for_key_if_exists(key)?;
+        let entry = self.table[found_index].take();
+        self.size -= 1;
+        self.move_stash_into_buckets();
+        self.shrink_if_underloaded();
+        entry
+    }
+
+    fn retain<F: FnMut(&K, &mut V) -> bool>(&mut self, mut f: F) {
+        for bucket in &mut self.table {
+            if let Some((key, value)) = bucket
+                && !f(key, value)
+            {
+                *bucket = None;
+                self.size -= 1;
+            }
+        }
+        self.move_stash_into_buckets();
+        self.shrink_if_underloaded();
+    }
+
+    fn clear(&mut self) {
+        self.table = hash_table::empty_buckets(self.capacity + STASH_SIZE);
+        self.size = 0;
+    }
+
+    fn iter<'a>(&'a self) -> impl ExactSizeIterator<Item = (&'a K, &'a V)>
+    where
+        K: 'a,
+        V: 'a,
+    {
+        hash_table::Iter::new(&self.table, self.size)
+    }
+
+    fn iter_mut<'a>(&'a mut self) -> impl ExactSizeIterator<Item = (&'a K, &'a mut V)>
+    where
+        K: 'a,
+        V: 'a,
+    {
+        hash_table::IterMut::new(&mut self.table, self.size)
+    }
+
+    fn drain(&mut self) -> impl ExactSizeIterator<Item = (K, V)> {
+        let len = self.size;
+        self.size = 0;
+        hash_table::Drain::new(&mut self.table, len)
+    }
+
+    fn shrink_to(&mut self, min_capacity: usize) {
+        let new_capacity = min_capacity.max(hash_table::min_capacity_for(self.size, self.max_load_factor));
+        if new_capacity.div_ceil(self.slots_per_bucket) < self.number_of_buckets() {
+            assert!(self.resize_to(new_capacity));
+        }
+    }
+}
+
+impl<K: Hash + Eq, V, S: BuildHasher> InsertAtBucket<K, V> for BucketizedCuckooHashTable<K, V, S> {
+    fn insert_at_bucket(&mut self, _index: usize, key: K, value: V) -> &mut V {
+        // Resize the table because it's getting too full.
+        if hash_table::exceeds_max_load_factor(self.size, self.capacity, self.max_load_factor) {
+            self.resize();
+        }
+        let index = self.insert_new_key(key, value);
+        match &mut self.table[index] {
+            Some((_, value)) => value,
+            None => unreachable!(),
+        }
+    }
+}
+
+impl<K: Hash + Eq, V, S: BuildHasher + Clone> MigrateBuckets<K, V> for BucketizedCuckooHashTable<K, V, S> {
+    fn capacity_to_resize_to(&self) -> Option<usize> {
+        hash_table::exceeds_max_load_factor(self.size, self.capacity, self.max_load_factor)
+            .then(|| hash_table::grown_capacity(self.capacity, self.growth_factor))
+    }
+
+    fn capacity_for(&self, len: usize) -> usize {
+        hash_table::min_capacity_for(len, self.max_load_factor)
+    }
+
+    fn empty_with_capacity(&self, capacity: usize) -> Self {
+        let mut hash_table = Self::with_hasher(capacity, self.hash_builder.clone());
+        hash_table.max_load_factor = self.max_load_factor;
+        hash_table.growth_factor = self.growth_factor;
+        hash_table.min_load_factor = self.min_load_factor;
+        hash_table.max_displacements = self.max_displacements;
+        hash_table.set_slots_per_bucket(self.slots_per_bucket);
+        hash_table
+    }
+
+    fn bucket_count(&self) -> usize {
+        self.table.len()
+    }
+
+    fn bucket_of(&self, key: &K) -> Option<usize> {
+        self.find_index_for_key_if_exists(key)
+    }
+
+    // Stashed entries stay in the stash until the whole table has been migrated, so that nothing moves into a
+    // bucket that was already taken.
+    fn take_bucket(&mut self, index: usize) -> impl Iterator<Item = (K, V)> {
+        let entry = self.table[index].take();
+        if entry.is_some() {
+            self.size -= 1;
+        }
+        entry.into_iter()
+    }
+}
+
+impl<K: Hash + Eq, V, S: BuildHasher> HashTableStats for BucketizedCuckooHashTable<K, V, S> {
+    fn stats(&self) -> Stats {
+        let hits = self.keys().map(|key| {
+            let position = self.lookup_slots(key).position(|slot| {
+                matches!(&self.table[slot], Some((existing_key, _)) if existing_key == key)
+            });
+            position.unwrap() + 1
+        });
+        // A miss checks every slot of both buckets and the stash.
+        let misses = std::iter::repeat_n(2 * self.slots_per_bucket + STASH_SIZE, self.number_of_buckets());
+        let mut stats = Stats::new(self.size, self.capacity, hits.collect(), misses.collect());
+        stats.longest_cluster = hash_table::longest_cluster(&self.table[..self.capacity]);
+        stats.cuckoo_displacements = Some(self.displacements.clone());
+        stats
+    }
+}
+
+impl<K, V, S> IntoIterator for BucketizedCuckooHashTable<K, V, S> {
+    type Item = (K, V);
+    type IntoIter = hash_table::IntoIter<Option<(K, V)>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        hash_table::IntoIter::new(self.table, self.size)
+    }
+}
+
+impl<'a, K, V, S> IntoIterator for &'a BucketizedCuckooHashTable<K, V, S> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = hash_table::Iter<'a, Option<(K, V)>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        hash_table::Iter::new(&self.table, self.size)
+    }
+}
+
+impl<'a, K, V, S> IntoIterator for &'a mut BucketizedCuckooHashTable<K, V, S> {
+    type Item = (&'a K, &'a mut V);
+    type IntoIter = hash_table::IterMut<'a, Option<(K, V)>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        hash_table::IterMut::new(&mut self.table, self.size)
+    }
+}
+
+// Fills a table with the max load factor at 1 until an insertion fails, and returns the load factor it
+// reached.
+fn measure_max_achievable_load_factor(slots_per_bucket: usize) -> f64 {
+    let mut hash_table = BucketizedCuckooHashTable::with_hasher(1 << 10, DeterministicState::default());
+    hash_table.set_max_load_factor(1.0);
+    hash_table.set_slots_per_bucket(slots_per_bucket);
+    let mut key = 0;
+    while hash_table.max_achievable_load_factor().is_none() {
+        hash_table.insert(key, key);
+        key += 1;
+    }
+    hash_table.max_achievable_load_factor().unwrap()
+}
+
+pub fn run() {
+    let capacity: usize = 10;
+    // The identity hasher keeps the layouts below the same from run to run.
+    let mut hash_table = BucketizedCuckooHashTable::with_hasher(capacity, BuildIdentityHasher::default());
+    // Let the table fill up completely so the evictions below stay visible.
+    hash_table.set_max_load_factor(1.0);
+
+    let digits = |key: usize| -> Vec<char> { key.to_string().chars().collect() };
+
+    for key in 1..=12 {
+        hash_table.insert(key, digits(key));
+    }
+    println!("Initial, 10 rounded up to 3 buckets of 4 slots and filled completely:");
+    hash_table.print();
+    assert!(hash_table.capacity == 12, "Capacity {} expected 12", hash_table.capacity);
+    assert!(hash_table.size == 12, "Size {}, expected 12", hash_table.size);
+    for key in 1..=12 {
+        assert!(hash_table.get(&key) == Some(&digits(key)), "Wrong value for key {}", key);
+    }
+
+    assert!(hash_table.remove(&4) == Some(digits(4)));
+    println!("After deleting key 4, which should have no impact on other keys:");
+    hash_table.print();
+    assert!(hash_table.get(&4).is_none());
+    assert!(hash_table.size == 11, "Size {}, expected 11", hash_table.size);
+    for key in (1..=12).filter(|&key| key != 4) {
+        assert!(hash_table.contains_key(&key), "Missing key {}", key);
+    }
+
+    // A table with one slot per bucket and no evictions puts every key whose two buckets are taken in the
+    // stash. Removing a key from one of those buckets moves the stashed key back into it.
+    let mut hash_table = BucketizedCuckooHashTable::with_hasher(capacity, BuildIdentityHasher::default());
+    hash_table.set_max_load_factor(1.0);
+    hash_table.set_slots_per_bucket(1);
+    hash_table.set_max_displacements(0);
+    let mut key = 0;
+    while hash_table.stash_len() == 0 {
+        hash_table.insert(key, ());
+        key += 1;
+    }
+    let stashed_key = key - 1;
+    println!("Inserted keys 0 to {}, the last of which should be in the stash:", stashed_key);
+    hash_table.print();
+    assert!(hash_table.find_index_for_key_if_exists(&stashed_key).unwrap() >= hash_table.capacity);
+    assert!(hash_table.rehash_count() == 0);
+    // The stashed key is only found after checking both of its single-slot buckets, and no placement displaced
+    // anything.
+    let stats = hash_table.stats();
+    assert!(stats.hits.max == 3 && stats.hits.histogram[3] == 1);
+    assert!(stats.cuckoo_displacements == Some(vec![key]));
+    let primary_slot = hash_table.slots(hash_table.get_primary_bucket(&stashed_key)).start;
+    let evicted_key = hash_table.table[primary_slot].as_ref().unwrap().0;
+    hash_table.remove(&evicted_key);
+    println!("Deleted {}, which should move {} out of the stash:", evicted_key, stashed_key);
+    hash_table.print();
+    assert!(hash_table.stash_len() == 0);
+    assert!(hash_table.find_index_for_key_if_exists(&stashed_key) == Some(primary_slot));
+
+    // Once the stash is full as well, the table is rehashed with new seeds.
+    while hash_table.rehash_count() == 0 {
+        hash_table.insert(key, ());
+        key += 1;
+    }
+    assert!(hash_table.size == key - 1, "Size {}, expected {}", hash_table.size, key - 1);
+    for key in (0..key).filter(|&key| key != evicted_key) {
+        assert!(hash_table.contains_key(&key), "Missing key {}", key);
+    }
+
+    // The breadth-first search finds the shortest eviction path, so every entry along it moves to its other
+    // bucket. Check that entries still sit in one of their two buckets or the stash after heavy use.
+    let mut hash_table = BucketizedCuckooHashTable::with_hasher(capacity, DeterministicState::default());
+    hash_table.set_max_load_factor(0.95);
+    for key in 0..5000 {
+        hash_table.insert(key, key);
+    }
+    for (slot, entry) in hash_table.table[..hash_table.capacity].iter().enumerate() {
+        if let Some((key, _)) = entry {
+            let bucket = slot / hash_table.slots_per_bucket;
+            assert!(bucket == hash_table.get_primary_bucket(key) || bucket == hash_table.get_secondary_bucket(key));
+        }
+    }
+    let displacements = hash_table.stats().cuckoo_displacements.unwrap();
+    println!("Displacements per placement: {:?}", displacements);
+    assert!(displacements.len() <= DEFAULT_MAX_DISPLACEMENTS + 1);
+
+    // More slots per bucket let the table fill up much further before an insertion fails.
+    let mut previous_load_factor = 0.0;
+    for slots_per_bucket in [1, 2, 4, 8] {
+        let load_factor = measure_max_achievable_load_factor(slots_per_bucket);
+        println!("Max achievable load factor with {} slots per bucket: {:.3}", slots_per_bucket, load_factor);
+        assert!(load_factor > previous_load_factor);
+        previous_load_factor = load_factor;
+    }
+    assert!(measure_max_achievable_load_factor(4) > 0.9);
+    println!("All tests passed");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    hash_table::hash_table_tests!(BucketizedCuckooHashTable);
+}
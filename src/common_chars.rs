@@ -1,16 +1,17 @@
 use crate::print_time;
 
-fn common_chars(arr: &Vec<String>) -> (String, usize) {
+/// Distinct characters of the first string that appear in every other string, and how many bytes they take up.
+pub fn common_chars(arr: &[String]) -> (String, usize) {
     let mut common = String::from("");
     'outer: for character in arr[0].chars() {
         if common.contains(character) { continue; }
-        for x in 1..arr.len() {
-            if !(arr[x].contains(character)) { continue 'outer; }
+        for word in &arr[1..] {
+            if !(word.contains(character)) { continue 'outer; }
         }
         common.push(character);
     }
     let l: usize = common.len();
-    return (common, l);
+    (common, l)
 }
 
 pub fn run() {
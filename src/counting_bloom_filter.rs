@@ -1,74 +1,143 @@
-struct CountingBloomFilter {
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hash};
+use std::marker::PhantomData;
+
+use crate::bloom_filter;
+use crate::hash_table::DeterministicState;
+
+/// A Bloom filter with a counter in place of each bit, so that items can be removed again.
+pub struct CountingBloomFilter<T: ?Sized, S = RandomState> {
     filter: Vec<usize>,
     capacity: usize,
+    hash_count: usize,
+    hash_builder: S,
+    marker: PhantomData<fn(&T)>,
+}
+
+impl<T: Hash + ?Sized> CountingBloomFilter<T> {
+    pub fn new(capacity: usize, hash_count: usize) -> Self {
+        Self::with_hasher(capacity, hash_count, RandomState::new())
+    }
+
+    /// Sized like `BloomFilter::with_rate`, with a counter in place of every bit.
+    pub fn with_rate(expected_items: usize, false_positive_rate: f64) -> Self {
+        Self::with_rate_and_hasher(expected_items, false_positive_rate, RandomState::new())
+    }
 }
 
-impl CountingBloomFilter {
-    fn new(capacity: usize) -> Self {
+impl<T: Hash + ?Sized, S: BuildHasher> CountingBloomFilter<T, S> {
+    pub fn with_hasher(capacity: usize, hash_count: usize, hash_builder: S) -> Self {
+        assert!(capacity > 0, "A Bloom filter needs at least one counter");
+        assert!(hash_count > 0, "A Bloom filter needs at least one hash function");
         CountingBloomFilter {
             filter: vec![0; capacity],
-            capacity
+            capacity,
+            hash_count,
+            hash_builder,
+            marker: PhantomData,
         }
     }
 
-    fn get_hashes(&self, item: usize) -> Vec<usize> {
-        return vec![item % self.capacity, (item + 10) % self.capacity];
+    pub fn with_rate_and_hasher(expected_items: usize, false_positive_rate: f64, hash_builder: S) -> Self {
+        let (capacity, hash_count) = bloom_filter::optimal_size(expected_items, false_positive_rate);
+        Self::with_hasher(capacity, hash_count, hash_builder)
     }
 
-    fn insert(&mut self, item: usize) {
-        for hash in self.get_hashes(item) {
-            self.filter[hash] += 1;
-        }
+    fn positions(&self, item: &T) -> impl Iterator<Item = usize> + use<T, S> {
+        bloom_filter::double_hashing_positions(self.hash_builder.hash_one(item), self.capacity, self.hash_count)
     }
 
-    fn remove(&mut self, item: usize) {
-        for hash in self.get_hashes(item) {
-            self.filter[hash] -= 1;
+    pub fn insert(&mut self, item: &T) {
+        for position in self.positions(item) {
+            self.filter[position] += 1;
         }
     }
 
-    fn contains(&self, item: usize) -> bool {
-        for hash in self.get_hashes(item) {
-            if self.filter[hash] == 0 {
-                return false;
-            }
+    /// Removes the item if the filter contains it and returns whether it did. An item that was never inserted is
+    /// left alone when one of its counters is zero, so no counter ever goes below zero. A false positive is removed
+    /// all the same, though, and takes away from the counters of the items that were inserted.
+    pub fn remove(&mut self, item: &T) -> bool {
+        if !self.contains(item) {
+            return false;
+        }
+        for position in self.positions(item) {
+            self.filter[position] -= 1;
         }
-        return true;
+        true
+    }
+
+    pub fn contains(&self, item: &T) -> bool {
+        self.positions(item).all(|position| self.filter[position] > 0)
+    }
+
+    fn nonzero_counters(&self) -> usize {
+        self.filter.iter().filter(|&&count| count > 0).count()
+    }
+
+    /// Chance that `contains` returns true for an item that isn't in the filter, given how many counters are
+    /// nonzero.
+    pub fn estimated_fp_rate(&self) -> f64 {
+        bloom_filter::estimated_fp_rate(self.nonzero_counters(), self.capacity, self.hash_count)
+    }
+
+    /// Number of distinct items in the filter, estimated from how many counters are nonzero. Infinite once none
+    /// of them is zero.
+    pub fn estimated_len(&self) -> f64 {
+        bloom_filter::estimated_len(self.nonzero_counters(), self.capacity, self.hash_count)
     }
 }
 
 pub fn run() {
-    let mut counting_bloom_filter = CountingBloomFilter::new(100);
-    counting_bloom_filter.insert(1);
-    counting_bloom_filter.insert(2);
-    counting_bloom_filter.insert(3);
-    counting_bloom_filter.insert(4);
-    counting_bloom_filter.insert(5);
-    counting_bloom_filter.insert(6);
-    counting_bloom_filter.insert(15);
-    counting_bloom_filter.insert(16);
-    counting_bloom_filter.insert(35);
-    counting_bloom_filter.insert(36);
-    assert!(counting_bloom_filter.contains(1));
-    assert!(counting_bloom_filter.contains(2));
-    assert!(counting_bloom_filter.contains(3));
-    assert!(counting_bloom_filter.contains(4));
-    assert!(counting_bloom_filter.contains(5));
-    assert!(counting_bloom_filter.contains(6));
-    assert!(counting_bloom_filter.contains(15));
-    assert!(counting_bloom_filter.contains(16));
-    assert!(counting_bloom_filter.contains(35));
-    assert!(counting_bloom_filter.contains(36));
-    // False positives are expected
-    assert!(counting_bloom_filter.contains(25));
-    assert!(counting_bloom_filter.contains(26));
-
-    counting_bloom_filter.remove(35);
-    counting_bloom_filter.remove(36);
-    assert!(!counting_bloom_filter.contains(35));
-    assert!(!counting_bloom_filter.contains(36));
-    // False positives should be gone with the removal of 35 and 36
-    assert!(!counting_bloom_filter.contains(25));
-    assert!(!counting_bloom_filter.contains(26));
+    let mut counting_bloom_filter = CountingBloomFilter::with_hasher(100, 3, DeterministicState::default());
+    let items = [1, 2, 3, 4, 5, 6, 15, 16, 35, 36];
+    for item in &items {
+        counting_bloom_filter.insert(item);
+    }
+    for item in &items {
+        assert!(counting_bloom_filter.contains(item));
+    }
+    // A hundred counters are few enough for items to share some, so the estimate is only rough.
+    println!("Estimated {:.1} items", counting_bloom_filter.estimated_len());
+    assert!((counting_bloom_filter.estimated_len() - 10.0).abs() < 2.0);
+
+    assert!(counting_bloom_filter.remove(&35));
+    assert!(counting_bloom_filter.remove(&36));
+    // Removing them again, or removing an item that was never inserted, finds a zero counter and changes nothing.
+    assert!(!counting_bloom_filter.remove(&35));
+    assert!(!counting_bloom_filter.remove(&1000));
+    assert!(!counting_bloom_filter.contains(&35));
+    assert!(!counting_bloom_filter.contains(&36));
+    for item in &items[..8] {
+        assert!(counting_bloom_filter.contains(item));
+    }
+    println!("Estimated {:.1} items after removing two", counting_bloom_filter.estimated_len());
+    assert!((counting_bloom_filter.estimated_len() - 8.0).abs() < 2.0);
+
+    // Removing everything that was inserted brings every counter back to zero, false positives included.
+    let mut counting_bloom_filter =
+        CountingBloomFilter::with_rate_and_hasher(1000, 0.01, DeterministicState::default());
+    for item in 0..1000 {
+        counting_bloom_filter.insert(&item);
+    }
+    println!(
+        "Sized for 1%: estimated false positive rate {:.2}%, with an estimated {:.0} items",
+        counting_bloom_filter.estimated_fp_rate() * 100.0,
+        counting_bloom_filter.estimated_len(),
+    );
+    assert!((counting_bloom_filter.estimated_fp_rate() - 0.01).abs() < 0.001);
+    assert!((counting_bloom_filter.estimated_len() - 1000.0).abs() < 20.0);
+    for item in 0..1000 {
+        assert!(counting_bloom_filter.remove(&item));
+    }
+    assert!(counting_bloom_filter.estimated_len() == 0.0);
+    assert!((0..2000).all(|item| !counting_bloom_filter.contains(&item)));
+
+    // Unsized items go in and come out by reference.
+    let mut words: CountingBloomFilter<str, _> =
+        CountingBloomFilter::with_rate_and_hasher(100, 0.01, DeterministicState::default());
+    words.insert("apple");
+    words.insert(&String::from("banana"));
+    assert!(words.remove("apple"));
+    assert!(!words.contains("apple") && words.contains("banana"));
     println!("All tests passed");
 }
@@ -1,174 +1,437 @@
-struct CuckooHashTable {
-    table: Vec<Option<(usize, Vec<char>)>>,
+use std::collections::hash_map::RandomState;
+use std::fmt::Debug;
+use std::hash::{BuildHasher, Hash};
+
+use crate::hash_table::{
+    self, BuildIdentityHasher, DeterministicState, Entry, HashTable, HashTableStats, InsertAtBucket, MigrateBuckets,
+    OccupiedEntry, Stats, VacantEntry,
+};
+
+const DEFAULT_MAX_DISPLACEMENTS: usize = 32;
+
+// Number of times the table is rehashed with fresh seeds at the same capacity before giving up and growing.
+const MAX_REHASHES_BEFORE_GROWING: usize = 3;
+
+// With two hash functions, cuckoo insertions start failing with high probability past half full.
+const DEFAULT_MAX_LOAD_FACTOR: f64 = 0.5;
+
+pub struct CuckooHashTable<K, V, S = RandomState> {
+    table: Vec<Option<(K, V)>>,
     capacity: usize,
     size: usize,
+    hash_builder: S,
+    max_load_factor: f64,
+    growth_factor: f64,
+    // When set, removals shrink the table once the load factor drops below this value.
+    min_load_factor: Option<f64>,
+    // The two hash functions are the key's hash mixed with each seed, so they are independent of each
+    // other and can both be replaced when a displacement cycle is found.
+    primary_seed: u64,
+    secondary_seed: u64,
+    max_displacements: usize,
+    rehash_count: usize,
+    // Number of placements, including the ones made while rehashing, that displaced each number of entries.
+    displacements: Vec<usize>,
 }
 
-impl CuckooHashTable {
-    fn new(capacity: usize) -> Self {
+impl<K: Hash + Eq, V> CuckooHashTable<K, V> {
+    pub fn new(capacity: usize) -> Self {
+        Self::with_hasher(capacity, RandomState::new())
+    }
+}
+
+impl<K: Hash + Eq, V, S: BuildHasher> CuckooHashTable<K, V, S> {
+    /// The capacity is at least one bucket, since lookups hash into the table before anything grows it.
+    pub fn with_hasher(capacity: usize, hash_builder: S) -> Self {
+        let capacity = capacity.max(1);
         CuckooHashTable {
-            table: vec![None; capacity],
+            table: hash_table::empty_buckets(capacity),
             capacity,
             size: 0,
+            hash_builder,
+            max_load_factor: DEFAULT_MAX_LOAD_FACTOR,
+            growth_factor: hash_table::DEFAULT_GROWTH_FACTOR,
+            min_load_factor: None,
+            primary_seed: hash_table::mix64(0),
+            secondary_seed: hash_table::mix64(1),
+            max_displacements: DEFAULT_MAX_DISPLACEMENTS,
+            rehash_count: 0,
+            displacements: Vec::new(),
         }
     }
 
-    fn get_primary_index(&self, key: usize) -> usize {
-        key % self.capacity
+    /// The table grows before an insertion would take it above this fraction of its capacity.
+    pub fn set_max_load_factor(&mut self, max_load_factor: f64) {
+        hash_table::assert_valid_max_load_factor(max_load_factor);
+        hash_table::assert_valid_min_load_factor(self.min_load_factor, max_load_factor);
+        self.max_load_factor = max_load_factor;
+    }
+
+    /// Makes removals shrink the table once its load factor drops below `min_load_factor`.
+    /// `None`, the default, never shrinks automatically.
+    pub fn set_min_load_factor(&mut self, min_load_factor: Option<f64>) {
+        hash_table::assert_valid_min_load_factor(min_load_factor, self.max_load_factor);
+        self.min_load_factor = min_load_factor;
+    }
+
+    /// Factor the capacity is multiplied by whenever the table grows.
+    pub fn set_growth_factor(&mut self, growth_factor: f64) {
+        hash_table::assert_valid_growth_factor(growth_factor);
+        self.growth_factor = growth_factor;
+    }
+
+    /// Maximum number of entries moved by a single insertion before the table is rehashed.
+    pub fn set_max_displacements(&mut self, max_displacements: usize) {
+        self.max_displacements = max_displacements;
+    }
+
+    /// Number of times the table was rebuilt with new seeds because an insertion could not be placed.
+    pub fn rehash_count(&self) -> usize {
+        self.rehash_count
+    }
+
+    fn get_index(&self, key: &K, seed: u64) -> usize {
+        (hash_table::mix64(self.hash_builder.hash_one(key) ^ seed) % self.capacity as u64) as usize
+    }
+
+    fn get_primary_index(&self, key: &K) -> usize {
+        self.get_index(key, self.primary_seed)
+    }
+
+    fn get_secondary_index(&self, key: &K) -> usize {
+        self.get_index(key, self.secondary_seed)
     }
 
-    fn get_secondary_index(&self, key: usize) -> usize {
-        (self.get_primary_index(key) + 2) % self.capacity
+    fn reseed(&mut self) {
+        self.primary_seed = hash_table::mix64(self.secondary_seed);
+        self.secondary_seed = hash_table::mix64(self.primary_seed);
+        self.rehash_count += 1;
     }
 
     fn resize_to(&mut self, new_capacity: usize) -> bool {
-        if new_capacity < self.capacity {
+        if !hash_table::fits_in_capacity(self.size, new_capacity, self.max_load_factor) {
             return false;
         }
         self.capacity = new_capacity;
-        let old_table = std::mem::take(&mut self.table);
-        self.table = vec![None; self.capacity];
+        let old_table = std::mem::replace(&mut self.table, hash_table::empty_buckets(self.capacity));
         self.size = 0;
-        for entry in old_table {
-            if let Some((key, value)) = entry {
-                self.insert(key, value);
-            }
+        for (key, value) in old_table.into_iter().flatten() {
+            self.insert_new_key(key, value);
+        }
+        true
+    }
+
+    // Shrinks the table if removals took it below the minimum load factor.
+    fn shrink_if_underloaded(&mut self) {
+        if let Some(min_load_factor) = self.min_load_factor
+            && (self.size as f64) < self.capacity as f64 * min_load_factor
+        {
+            self.shrink_to(hash_table::shrunk_capacity(self.size, min_load_factor, self.max_load_factor));
         }
-        return true;
     }
 
     fn resize(&mut self) {
-        assert!(self.resize_to(self.capacity * 2));
+        assert!(self.resize_to(hash_table::grown_capacity(self.capacity, self.growth_factor)));
     }
 
-    fn insert(&mut self, key: usize, value: Vec<char>) {
-        // Resize the table because it's full.
-        if self.size >= self.capacity {
-            self.resize();
+    // Walks the displacement chain that starts by evicting the occupant of `start_index` and returns the
+    // buckets visited, ending with an empty one. Returns None if the chain revisits a bucket (a cycle) or
+    // would need more than `max_displacements` moves.
+    fn find_displacement_path(&self, start_index: usize) -> Option<Vec<usize>> {
+        let mut path = vec![start_index];
+        let mut current_index = start_index;
+        for _ in 0..self.max_displacements {
+            let (occupant_key, _) = self.table[current_index].as_ref()?;
+            let primary_index = self.get_primary_index(occupant_key);
+            let next_index = if current_index == primary_index {
+                self.get_secondary_index(occupant_key)
+            } else {
+                primary_index
+            };
+            if path.contains(&next_index) {
+                return None;
+            }
+            path.push(next_index);
+            if self.table[next_index].is_none() {
+                return Some(path);
+            }
+            current_index = next_index;
         }
+        None
+    }
 
-        let mut primary_index = self.get_primary_index(key);
-        let mut secondary_index = self.get_secondary_index(key);
-
-        if let Some((existing_primary_key, _)) = self.table[primary_index] {
-            if existing_primary_key == key {
-                self.table[primary_index] = Some((key, value));
+    // Places a key known not to be in the table and returns the bucket it ended up in.
+    fn insert_new_key(&mut self, key: K, value: V) -> usize {
+        let mut rehashes = 0;
+        loop {
+            let primary_index = self.get_primary_index(&key);
+            let secondary_index = self.get_secondary_index(&key);
+            let path = if self.table[primary_index].is_none() {
+                Some(vec![primary_index])
             } else if self.table[secondary_index].is_none() {
-                self.table[secondary_index] = Some((key, value));
-                self.size += 1;
+                Some(vec![secondary_index])
             } else {
-                let existing_secondary_key = self.table[secondary_index].as_ref().unwrap().0.clone();
-                let existing_secondary_value = self.table[secondary_index].as_ref().unwrap().1.clone();
-                if existing_secondary_key == key {
-                    self.table[secondary_index] = Some((key, value));
-                } else {
-                    // Start displacement chain, beginning with the secondary index
-                    self.table[secondary_index] = Some((key, value));
-                    self.size += 1;
-                    let mut excluded_index = secondary_index;
-                    let mut prev_key = existing_secondary_key;
-                    let mut prev_val = existing_secondary_value.clone();
-                    primary_index = self.get_primary_index(prev_key);
-                    secondary_index = self.get_secondary_index(prev_key);
-                    let mut i: usize = 0;
-                    let mut target_index = {
-                        if excluded_index == primary_index {
-                            secondary_index
-                        } else {
-                            primary_index
-                        }
-                    };
-                    loop {
-                        if self.table[target_index].is_none() {
-                            self.table[target_index] = Some((prev_key, prev_val));
-                            return;
-                        }
-                        let existing_key = self.table[target_index].as_ref().unwrap().0.clone();
-                        let existing_value = self.table[target_index].as_ref().unwrap().1.clone();
-                        self.table[target_index] = Some((prev_key, prev_val));
-                        prev_key = existing_key.clone();
-                        prev_val = existing_value.clone();
-                        excluded_index = target_index;
-
-                        i += 1;
-                        if i >= self.capacity {
-                            self.resize();
-                            // The resize function resets the size; so we have to increment it again.
-                            self.size += 1;
-                            i = 0;
-                        }
-
-                        primary_index = self.get_primary_index(prev_key);
-                        secondary_index = self.get_secondary_index(prev_key);
-                        if excluded_index == primary_index {
-                            target_index = secondary_index;
-                        } else {
-                            target_index = primary_index;
-                        }
-                    }
+                self.find_displacement_path(primary_index)
+                    .or_else(|| self.find_displacement_path(secondary_index))
+            };
+
+            if let Some(path) = path {
+                // Shift every entry on the path one step along, starting from the empty end, then put the
+                // new entry in the bucket that was freed at the front.
+                for i in (1..path.len()).rev() {
+                    self.table[path[i]] = self.table[path[i - 1]].take();
                 }
+                self.table[path[0]] = Some((key, value));
+                self.size += 1;
+                hash_table::add_to_histogram(&mut self.displacements, path.len() - 1);
+                return path[0];
+            }
+
+            // A cycle or an overly long chain means these hash functions can't place the key. Try new
+            // seeds a few times before paying for a bigger table.
+            if rehashes < MAX_REHASHES_BEFORE_GROWING {
+                rehashes += 1;
+                self.reseed();
+                assert!(self.resize_to(self.capacity));
+            } else {
+                rehashes = 0;
+                self.resize();
             }
-        } else {
-            self.table[primary_index] = Some((key, value));
-            self.size += 1;
         }
     }
 
-    fn find_index_for_key_if_exists(&mut self, key: usize) -> Option<usize> {
+    fn find_index_for_key_if_exists(&self, key: &K) -> Option<usize> {
         let primary_index = self.get_primary_index(key);
-        if let Some((existing_primary_key, _)) = &self.table[primary_index] {
-            if *existing_primary_key == key {
-                return Some(primary_index);
-            }
+        if let Some((existing_primary_key, _)) = &self.table[primary_index]
+            && existing_primary_key == key
+        {
+            return Some(primary_index);
         }
 
         let secondary_index = self.get_secondary_index(key);
-        if let Some((existing_secondary_key, _)) = &self.table[secondary_index] {
-            if *existing_secondary_key == key {
-                return Some(secondary_index);
+        if let Some((existing_secondary_key, _)) = &self.table[secondary_index]
+            && existing_secondary_key == key
+        {
+            return Some(secondary_index);
+        }
+
+        None
+    }
+}
+
+impl<K: Debug, V: Debug, S> CuckooHashTable<K, V, S> {
+    pub fn print(&self) {
+        println!("Capacity: {}", self.capacity);
+        println!("Size: {}", self.size);
+        println!("Entries:");
+        for entry in &self.table {
+            if let Some((key, value)) = entry {
+                println!("\t{:?}: {:?}", key, value);
+            } else {
+                println!("\t<None>");
             }
         }
+    }
+}
 
-        return None;
+impl<K: Hash + Eq, V, S: BuildHasher> HashTable<K, V> for CuckooHashTable<K, V, S> {
+    fn len(&self) -> usize {
+        self.size
     }
 
-    fn get(&mut self, key: usize) -> Option<&Vec<char>> {
-        let found_index = self.find_index_for_key_if_exists(key);
-        if found_index.is_none() {
-            return None;
+    fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    fn entry(&mut self, key: K) -> Entry<'_, K, V> {
+        match self.find_index_for_key_if_exists(&key) {
+            Some(index) => Entry::Occupied(OccupiedEntry::from_bucket(&mut self.table[index])),
+            // Cuckoo insertion picks the bucket itself, since it may have to evict entries or grow first.
+            None => Entry::Vacant(VacantEntry::new(key, 0, self)),
         }
-        if let Some((_key, value)) = &self.table[found_index.unwrap()] {
-            return Some(value);
+    }
+
+    fn get_key_value(&self, key: &K) -> Option<(&K, &V)> {
+        let found_index = self.find_index_for_key_if_exists(key)?;
+        if let Some((key, value)) = &self.table[found_index] {
+            Some((key, value))
         } else {
-            return None;
+            None
         }
     }
 
-    fn delete(&mut self, key: usize) {
-        let found_index = self.find_index_for_key_if_exists(key);
-        if found_index.is_some() {
-            self.table[found_index.unwrap()] = None;
-            self.size -= 1;
+    fn get_mut(&mut self, key: &K) -> Option<&mut V> {
+        let found_index = self.find_index_for_key_if_exists(key)?;
+        if let Some((_key, value)) = &mut self.table[found_index] {
+            Some(value)
+        } else {
+            None
         }
     }
 
-    fn print(&self) {
-        println!("Capacity: {}", self.capacity);
-        println!("Size: {}", self.size);
-        println!("Entries:");
-        for entry in &self.table {
-            if let Some((key, value)) = entry {
-                println!("\t{}: {}", key, value.iter().collect::<String>());
-            } else {
-                println!("\t<None>");
+    fn get_many_mut<const N: usize>(&mut self, keys: [&K; N]) -> Option<[&mut V; N]> {
+        let mut indices = [0; N];
+        for (index, key) in indices.iter_mut().zip(keys) {
+            *index = self.find_index_for_key_if_exists(key)?;
+        }
+        hash_table::values_at_disjoint_buckets(&mut self.table, indices)
+    }
+
+    fn remove_entry(&mut self, key: &K) -> Option<(K, V)> {
+        let found_index = self.find_index_for_key_if_exists(key)?;
+        let entry = self.table[found_index].take();
+        self.size -= 1;
+        self.shrink_if_underloaded();
+        entry
+    }
+
+    fn retain<F: FnMut(&K, &mut V) -> bool>(&mut self, mut f: F) {
+        for bucket in &mut self.table {
+            if let Some((key, value)) = bucket
+                && !f(key, value)
+            {
+                *bucket = None;
+                self.size -= 1;
             }
         }
+        self.shrink_if_underloaded();
+    }
+
+    fn clear(&mut self) {
+        self.table = hash_table::empty_buckets(self.capacity);
+        self.size = 0;
+    }
+
+    fn iter<'a>(&'a self) -> impl ExactSizeIterator<Item = (&'a K, &'a V)>
+    where
+        K: 'a,
+        V: 'a,
+    {
+        hash_table::Iter::new(&self.table, self.size)
+    }
+
+    fn iter_mut<'a>(&'a mut self) -> impl ExactSizeIterator<Item = (&'a K, &'a mut V)>
+    where
+        K: 'a,
+        V: 'a,
+    {
+        hash_table::IterMut::new(&mut self.table, self.size)
+    }
+
+    fn drain(&mut self) -> impl ExactSizeIterator<Item = (K, V)> {
+        let len = self.size;
+        self.size = 0;
+        hash_table::Drain::new(&mut self.table, len)
+    }
+
+    fn shrink_to(&mut self, min_capacity: usize) {
+        let new_capacity = min_capacity.max(hash_table::min_capacity_for(self.size, self.max_load_factor));
+        if new_capacity < self.capacity {
+            assert!(self.resize_to(new_capacity));
+        }
+    }
+}
+
+impl<K: Hash + Eq, V, S: BuildHasher> InsertAtBucket<K, V> for CuckooHashTable<K, V, S> {
+    fn insert_at_bucket(&mut self, _index: usize, key: K, value: V) -> &mut V {
+        // Resize the table because it's getting too full.
+        if hash_table::exceeds_max_load_factor(self.size, self.capacity, self.max_load_factor) {
+            self.resize();
+        }
+        let index = self.insert_new_key(key, value);
+        match &mut self.table[index] {
+            Some((_, value)) => value,
+            None => unreachable!(),
+        }
+    }
+}
+
+impl<K: Hash + Eq, V, S: BuildHasher + Clone> MigrateBuckets<K, V> for CuckooHashTable<K, V, S> {
+    fn capacity_to_resize_to(&self) -> Option<usize> {
+        hash_table::exceeds_max_load_factor(self.size, self.capacity, self.max_load_factor)
+            .then(|| hash_table::grown_capacity(self.capacity, self.growth_factor))
+    }
+
+    fn capacity_for(&self, len: usize) -> usize {
+        hash_table::min_capacity_for(len, self.max_load_factor)
+    }
+
+    fn empty_with_capacity(&self, capacity: usize) -> Self {
+        let mut hash_table = Self::with_hasher(capacity, self.hash_builder.clone());
+        hash_table.max_load_factor = self.max_load_factor;
+        hash_table.growth_factor = self.growth_factor;
+        hash_table.min_load_factor = self.min_load_factor;
+        hash_table.max_displacements = self.max_displacements;
+        hash_table
+    }
+
+    fn bucket_count(&self) -> usize {
+        self.table.len()
+    }
+
+    fn bucket_of(&self, key: &K) -> Option<usize> {
+        self.find_index_for_key_if_exists(key)
+    }
+
+    fn take_bucket(&mut self, index: usize) -> impl Iterator<Item = (K, V)> {
+        let entry = self.table[index].take();
+        if entry.is_some() {
+            self.size -= 1;
+        }
+        entry.into_iter()
+    }
+}
+
+impl<K: Hash + Eq, V, S: BuildHasher> HashTableStats for CuckooHashTable<K, V, S> {
+    fn stats(&self) -> Stats {
+        let hits = self.keys().map(|key| {
+            match &self.table[self.get_primary_index(key)] {
+                Some((existing_key, _)) if existing_key == key => 1,
+                _ => 2,
+            }
+        });
+        // A miss always checks both of the key's buckets.
+        let misses = std::iter::repeat_n(2, self.capacity);
+        let mut stats = Stats::new(self.size, self.capacity, hits.collect(), misses.collect());
+        stats.longest_cluster = hash_table::longest_cluster(&self.table);
+        stats.cuckoo_displacements = Some(self.displacements.clone());
+        stats
+    }
+}
+
+impl<K, V, S> IntoIterator for CuckooHashTable<K, V, S> {
+    type Item = (K, V);
+    type IntoIter = hash_table::IntoIter<Option<(K, V)>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        hash_table::IntoIter::new(self.table, self.size)
+    }
+}
+
+impl<'a, K, V, S> IntoIterator for &'a CuckooHashTable<K, V, S> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = hash_table::Iter<'a, Option<(K, V)>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        hash_table::Iter::new(&self.table, self.size)
+    }
+}
+
+impl<'a, K, V, S> IntoIterator for &'a mut CuckooHashTable<K, V, S> {
+    type Item = (&'a K, &'a mut V);
+    type IntoIter = hash_table::IterMut<'a, Option<(K, V)>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        hash_table::IterMut::new(&mut self.table, self.size)
     }
 }
 
 pub fn run() {
     let capacity: usize= 10;
-    let mut hash_table = CuckooHashTable::new(capacity);
+    // The identity hasher keeps the layouts below the same from run to run.
+    let mut hash_table = CuckooHashTable::with_hasher(capacity, BuildIdentityHasher::default());
+    // Let the table fill up completely so the collisions below stay visible.
+    hash_table.set_max_load_factor(1.0);
 
     let one: Vec<char> = "one".chars().collect();
     let two: Vec<char> = "two".chars().collect();
@@ -196,42 +459,41 @@ pub fn run() {
     hash_table.insert(6, six.clone());
     hash_table.insert(7, seven.clone());
     hash_table.insert(8, eight.clone());
-    // Should collide with 1 and shift everything after to the back
     hash_table.insert(11, eleven.clone());
     println!("Initial:");
     hash_table.print();
     assert!(hash_table.capacity == 10, "Capacity {} expected 10", hash_table.capacity);
     assert!(hash_table.size == 9, "Size {}, expected 9", hash_table.size);
-    let mut return_val = hash_table.get(1);
+    let mut return_val = hash_table.get(&1);
     assert!(return_val.is_some());
     assert!(return_val.unwrap().iter().zip(one.clone()).filter(|&(a, b)| *a != b).count() == 0);
-    return_val = hash_table.get(2);
+    return_val = hash_table.get(&2);
     assert!(return_val.is_some());
     assert!(return_val.unwrap().iter().zip(two.clone()).filter(|&(a, b)| *a != b).count() == 0);
-    return_val = hash_table.get(3);
+    return_val = hash_table.get(&3);
     assert!(return_val.is_some());
     assert!(return_val.unwrap().iter().zip(three.clone()).filter(|&(a, b)| *a != b).count() == 0);
-    return_val = hash_table.get(4);
+    return_val = hash_table.get(&4);
     assert!(return_val.is_some());
     assert!(return_val.unwrap().iter().zip(four.clone()).filter(|&(a, b)| *a != b).count() == 0);
-    return_val = hash_table.get(5);
+    return_val = hash_table.get(&5);
     assert!(return_val.is_some());
     assert!(return_val.unwrap().iter().zip(five.clone()).filter(|&(a, b)| *a != b).count() == 0);
-    return_val = hash_table.get(6);
+    return_val = hash_table.get(&6);
     assert!(return_val.is_some());
     assert!(return_val.unwrap().iter().zip(six.clone()).filter(|&(a, b)| *a != b).count() == 0);
-    return_val = hash_table.get(7);
+    return_val = hash_table.get(&7);
     assert!(return_val.is_some());
     assert!(return_val.unwrap().iter().zip(seven.clone()).filter(|&(a, b)| *a != b).count() == 0);
-    return_val = hash_table.get(8);
+    return_val = hash_table.get(&8);
     assert!(return_val.is_some());
     assert!(return_val.unwrap().iter().zip(eight.clone()).filter(|&(a, b)| *a != b).count() == 0);
-    return_val = hash_table.get(11);
+    return_val = hash_table.get(&11);
     assert!(return_val.is_some());
     assert!(return_val.unwrap().iter().zip(eleven.clone()).filter(|&(a, b)| *a != b).count() == 0);
 
-    hash_table.delete(4);
-    return_val = hash_table.get(4);
+    hash_table.remove(&4);
+    return_val = hash_table.get(&4);
     assert!(return_val.is_none());
     println!("After deleting key 4, which should have no impact on other keys:");
     hash_table.print();
@@ -252,11 +514,11 @@ pub fn run() {
     hash_table.print();
     assert!(hash_table.size == 10, "Size {}, expected 10", hash_table.size);
 
-    hash_table.delete(12);
+    hash_table.remove(&12);
     println!("Deleted 12, no impact on other keys:");
     hash_table.print();
     assert!(hash_table.size == 9, "Size {}, expected 9", hash_table.size);
-    assert!(hash_table.get(12).is_none());
+    assert!(hash_table.get(&12).is_none());
 
     hash_table.insert(9, nine.clone());
     hash_table.insert(10, ten.clone());
@@ -264,36 +526,96 @@ pub fn run() {
     hash_table.insert(16, sixteen.clone());
     hash_table.insert(36, thirty_six.clone());
     hash_table.insert(56, fifty_six.clone());
-    println!("Inserted 9, 10, 12, 16, 36, 56, table should be resized to 40:");
+    println!("Inserted 9, 10, 12, 16, 36, 56, table should be resized to 20:");
     hash_table.print();
-    assert!(hash_table.capacity == 40, "Capacity {} expected 40", hash_table.capacity);
+    assert!(hash_table.capacity == 20, "Capacity {} expected 20", hash_table.capacity);
     assert!(hash_table.size == 15, "Size {}, expected 15", hash_table.size);
 
     hash_table.insert(15, fifteen.clone());
     hash_table.insert(35, thirty_five.clone());
-    println!("Inserted 15 & 35, keys 16, 36 & 56 should be moved toward the back:");
+    println!("Inserted 15 & 35:");
     hash_table.print();
-    assert!(hash_table.capacity == 40, "Capacity {} expected 40", hash_table.capacity);
+    assert!(hash_table.capacity == 20, "Capacity {} expected 20", hash_table.capacity);
     assert!(hash_table.size == 17, "Size {}, expected 17", hash_table.size);
-    assert!(hash_table.get(15).is_some());
-    assert!(hash_table.get(35).is_some());
+    assert!(hash_table.get(&15).is_some());
+    assert!(hash_table.get(&35).is_some());
 
-    hash_table.delete(36);
-    println!("Deleted 36, keys 16 & 56 should be moved toward the front:");
+    hash_table.remove(&36);
+    println!("Deleted 36, which should have no impact on other keys:");
     hash_table.print();
-    assert!(hash_table.capacity == 40, "Capacity {} expected 40", hash_table.capacity);
+    assert!(hash_table.capacity == 20, "Capacity {} expected 20", hash_table.capacity);
     assert!(hash_table.size == 16, "Size {}, expected 16", hash_table.size);
-    assert!(hash_table.get(35).is_some());
-    assert!(hash_table.get(56).is_some());
-    assert!(hash_table.get(16).is_some());
-    assert!(hash_table.get(36).is_none());
+    assert!(hash_table.get(&35).is_some());
+    assert!(hash_table.get(&56).is_some());
+    assert!(hash_table.get(&16).is_some());
+    assert!(hash_table.get(&36).is_none());
 
-    hash_table.delete(35);
-    println!("Deleted 35, keys 16 & 56 should be moved toward the front:");
+    hash_table.remove(&35);
+    println!("Deleted 35, which should have no impact on other keys:");
     hash_table.print();
-    assert!(hash_table.capacity == 40, "Capacity {} expected 40", hash_table.capacity);
+    assert!(hash_table.capacity == 20, "Capacity {} expected 20", hash_table.capacity);
     assert!(hash_table.size == 15, "Size {}, expected 15", hash_table.size);
-    assert!(hash_table.get(36).is_none());
-    assert!(hash_table.get(56).is_some());
-    assert!(hash_table.get(16).is_some());
+    assert!(hash_table.get(&36).is_none());
+    assert!(hash_table.get(&56).is_some());
+    assert!(hash_table.get(&16).is_some());
+
+    // Keys that share a primary bucket should be spread over different secondary buckets.
+    let hash_table: CuckooHashTable<usize, (), _> = CuckooHashTable::with_hasher(100, BuildIdentityHasher::default());
+    let primary_index = hash_table.get_primary_index(&0);
+    let secondary_indexes: Vec<usize> = (1..10_000)
+        .filter(|key| hash_table.get_primary_index(key) == primary_index)
+        .map(|key| hash_table.get_secondary_index(&key))
+        .collect();
+    assert!(secondary_indexes.len() > 10);
+    assert!(secondary_indexes.iter().any(|index| *index != secondary_indexes[0]));
+
+    // Without any displacements, every insertion whose two buckets are taken forces a rehash with new
+    // seeds, and eventually a resize.
+    let mut hash_table = CuckooHashTable::with_hasher(capacity, BuildIdentityHasher::default());
+    hash_table.set_max_displacements(0);
+    for key in 0..100 {
+        hash_table.insert(key, key);
+    }
+    println!(
+        "Rehashes with no displacements allowed: {}, capacity: {}",
+        hash_table.rehash_count(),
+        hash_table.capacity,
+    );
+    assert!(hash_table.rehash_count() > 0);
+    assert!(hash_table.size == 100, "Size {}, expected 100", hash_table.size);
+    for key in 0..100 {
+        assert!(hash_table.get(&key) == Some(&key));
+    }
+    let displacements = hash_table.stats().cuckoo_displacements.unwrap();
+    assert!(displacements.len() == 1 && displacements[0] >= 100, "Displacements: {:?}", displacements);
+
+    // With displacements allowed, most placements still land in a free bucket, and none walk further than
+    // the limit.
+    let mut hash_table = CuckooHashTable::with_hasher(capacity, DeterministicState::default());
+    for key in 0..1000 {
+        hash_table.insert(key, key);
+    }
+    let stats = hash_table.stats();
+    let displacements = stats.cuckoo_displacements.as_ref().unwrap();
+    println!("Displacements per placement: {:?}", displacements);
+    assert!(displacements[0] > displacements.iter().skip(1).sum());
+    assert!(displacements.len() <= DEFAULT_MAX_DISPLACEMENTS + 1);
+    assert!(stats.hits.max <= 2 && stats.misses.average() == 2.0);
+
+    // A table created with no capacity gets one bucket, and grows from there.
+    let mut hash_table = CuckooHashTable::new(0);
+    assert!(hash_table.capacity == 1, "Capacity {}, expected 1", hash_table.capacity);
+    assert!(hash_table.get(&1).is_none());
+    for key in 0..100 {
+        hash_table.insert(key, key);
+    }
+    assert!((0..100).all(|key| hash_table.get(&key) == Some(&key)));
+    println!("All tests passed");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    hash_table::hash_table_tests!(CuckooHashTable);
 }
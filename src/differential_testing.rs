@@ -0,0 +1,304 @@
+use std::collections::HashMap;
+use std::panic::{self, AssertUnwindSafe};
+
+use alg::bucketized_cuckoo_hash_table::BucketizedCuckooHashTable;
+use alg::cuckoo_hash_table::CuckooHashTable;
+use alg::dhts_hash_table::DoubleHashingTombstoneHashTable;
+use alg::hash_table::{self, BuildIdentityHasher, DeterministicState, HashTable};
+use alg::hopscotch_hash_table::HopscotchHashTable;
+use alg::incremental_hash_table::IncrementalHashTable;
+use alg::lprh_hash_table::LinearProbingRobinHoodHashTable;
+use alg::lpts_hash_table::LinearProbingTombstoneHashTable;
+use alg::qpts_hash_table::QuadraticProbingTombstoneHashTable;
+use alg::sc_hash_table::{LinkedListChainingHashTable, SmallVecChainingHashTable};
+use alg::swiss_hash_table::SwissHashTable;
+
+// Few enough distinct keys that sequences keep hitting keys already in the table, and removals find something.
+const KEY_RANGE: usize = 64;
+
+const MAX_OPERATIONS: usize = 300;
+
+/// A single step of a generated test case, applied to a table and to `std::collections::HashMap` alike.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Operation {
+    Insert(usize, usize),
+    Get(usize),
+    // Adds one to the value, if the key is present.
+    GetMut(usize),
+    Remove(usize),
+    // Keeps only the keys that aren't multiples of the divisor.
+    Retain(usize),
+    ShrinkTo(usize),
+    Clear,
+}
+
+/// Splitmix64 stream, so that every failure can be reproduced from its seed.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(1);
+        hash_table::mix64(self.0)
+    }
+
+    fn below(&mut self, bound: usize) -> usize {
+        (self.next() % bound as u64) as usize
+    }
+}
+
+fn generate_operations(seed: u64) -> Vec<Operation> {
+    let mut rng = Rng(seed);
+    let len = rng.below(MAX_OPERATIONS + 1);
+    (0..len)
+        .map(|_| match rng.below(100) {
+            // Mostly insertions, so that tables grow through several resizes.
+            0..40 => Operation::Insert(rng.below(KEY_RANGE), rng.below(1000)),
+            40..60 => Operation::Get(rng.below(KEY_RANGE)),
+            60..70 => Operation::GetMut(rng.below(KEY_RANGE)),
+            70..94 => Operation::Remove(rng.below(KEY_RANGE)),
+            94..97 => Operation::Retain(rng.below(4) + 2),
+            97..99 => Operation::ShrinkTo(rng.below(KEY_RANGE)),
+            _ => Operation::Clear,
+        })
+        .collect()
+}
+
+// Applies `operation` to both maps and returns a description of the first difference between them.
+fn apply<T: HashTable<usize, usize>>(
+    hash_table: &mut T,
+    model: &mut HashMap<usize, usize>,
+    operation: Operation,
+) -> Result<(), String> {
+    let (actual, expected) = match operation {
+        Operation::Insert(key, value) => {
+            hash_table.insert(key, value);
+            model.insert(key, value);
+            (None, None)
+        }
+        Operation::Get(key) => (hash_table.get(&key).copied(), model.get(&key).copied()),
+        Operation::GetMut(key) => {
+            let actual = hash_table.get_mut(&key).map(|value| {
+                *value += 1;
+                *value
+            });
+            let expected = model.get_mut(&key).map(|value| {
+                *value += 1;
+                *value
+            });
+            (actual, expected)
+        }
+        Operation::Remove(key) => (hash_table.remove(&key), model.remove(&key)),
+        Operation::Retain(divisor) => {
+            hash_table.retain(|key, _| key % divisor != 0);
+            model.retain(|key, _| key % divisor != 0);
+            (None, None)
+        }
+        Operation::ShrinkTo(min_capacity) => {
+            hash_table.shrink_to(min_capacity);
+            (None, None)
+        }
+        Operation::Clear => {
+            hash_table.clear();
+            model.clear();
+            (None, None)
+        }
+    };
+    if actual != expected {
+        return Err(format!("{:?} returned {:?}, expected {:?}", operation, actual, expected));
+    }
+    if hash_table.len() != model.len() {
+        return Err(format!("After {:?}, len is {}, expected {}", operation, hash_table.len(), model.len()));
+    }
+    Ok(())
+}
+
+// Runs `operations` against a fresh table and the model, then compares their contents.
+fn run_operations<T: HashTable<usize, usize>>(
+    new_table: &impl Fn() -> T,
+    operations: &[Operation],
+) -> Result<(), String> {
+    let mut hash_table = new_table();
+    let mut model = HashMap::new();
+    // A panicking table counts as a divergence, and gets shrunk like any other.
+    panic::catch_unwind(AssertUnwindSafe(|| {
+        for &operation in operations {
+            apply(&mut hash_table, &mut model, operation)?;
+        }
+        let mut entries: Vec<(usize, usize)> = hash_table.iter().map(|(key, value)| (*key, *value)).collect();
+        let mut expected: Vec<(usize, usize)> = model.iter().map(|(key, value)| (*key, *value)).collect();
+        entries.sort_unstable();
+        expected.sort_unstable();
+        if entries != expected {
+            return Err(format!("Iterated over {:?}, expected {:?}", entries, expected));
+        }
+        Ok(())
+    }))
+    .unwrap_or_else(|payload| {
+        let message = payload
+            .downcast_ref::<String>()
+            .map(String::as_str)
+            .or_else(|| payload.downcast_ref::<&str>().copied())
+            .unwrap_or("unknown panic");
+        Err(format!("Panicked: {}", message))
+    })
+}
+
+// Smaller variants of `operation` to try while shrinking, simplest first.
+fn simplifications(operation: Operation) -> Vec<Operation> {
+    let mut simpler = Vec::new();
+    match operation {
+        Operation::Insert(key, value) => {
+            if value != 0 {
+                simpler.push(Operation::Insert(key, 0));
+            }
+            if key != 0 {
+                simpler.push(Operation::Insert(key / 2, value));
+            }
+        }
+        Operation::Get(key) | Operation::GetMut(key) | Operation::Remove(key) if key != 0 => {
+            simpler.push(match operation {
+                Operation::Get(_) => Operation::Get(key / 2),
+                Operation::GetMut(_) => Operation::GetMut(key / 2),
+                _ => Operation::Remove(key / 2),
+            });
+        }
+        Operation::ShrinkTo(min_capacity) if min_capacity != 0 => simpler.push(Operation::ShrinkTo(0)),
+        _ => {}
+    }
+    simpler
+}
+
+/// Shrinks a failing sequence to one where removing any single operation, or simplifying any operation,
+/// makes the failure go away. Drops ever smaller chunks of operations, then simplifies the ones left, and
+/// starts over until neither helps.
+pub fn shrink(mut operations: Vec<Operation>, mut fails: impl FnMut(&[Operation]) -> bool) -> Vec<Operation> {
+    loop {
+        let mut shrunk = false;
+        let mut chunk_len = operations.len().div_ceil(2);
+        while chunk_len > 0 {
+            let mut start = 0;
+            while start < operations.len() {
+                let end = (start + chunk_len).min(operations.len());
+                let candidate: Vec<Operation> = [&operations[..start], &operations[end..]].concat();
+                if fails(&candidate) {
+                    operations = candidate;
+                    shrunk = true;
+                } else {
+                    start += chunk_len;
+                }
+            }
+            chunk_len /= 2;
+        }
+
+        for i in 0..operations.len() {
+            // Simplifications only ever lead to simpler operations, so this ends.
+            while let Some(candidate) = simplifications(operations[i]).into_iter().find_map(|simpler| {
+                let mut candidate = operations.clone();
+                candidate[i] = simpler;
+                fails(&candidate).then_some(candidate)
+            }) {
+                operations = candidate;
+                shrunk = true;
+            }
+        }
+
+        if !shrunk {
+            return operations;
+        }
+    }
+}
+
+/// Applies `cases` random sequences of operations to tables built by `new_table` and to a `HashMap`, and
+/// panics with a minimal failing sequence if they ever disagree.
+pub fn check_against_std<T: HashTable<usize, usize>>(name: &str, cases: u64, new_table: impl Fn() -> T) {
+    for seed in 0..cases {
+        let operations = generate_operations(seed);
+        if run_operations(&new_table, &operations).is_err() {
+            let shrunk = shrink(operations, |operations| run_operations(&new_table, operations).is_err());
+            let error = run_operations(&new_table, &shrunk).unwrap_err();
+            panic!("{} diverged from HashMap with seed {}: {}\nMinimal sequence: {:?}", name, seed, error, shrunk);
+        }
+    }
+}
+
+// Checks a table with a well-mixed hasher, and with the identity hasher, which piles keys up in neighboring
+// buckets and makes tiny tables collide constantly. Each is checked with the default settings, and then configured
+// to grow by non-integer factors, to fill up completely, and to shrink once removals empty it out.
+macro_rules! check_with_both_hashers {
+    ($name:literal, $cases:expr, $table:ident $(, $arg:expr)*) => {{
+        check_with_both_hashers!(@hasher $name, $cases, $table, DeterministicState::default() $(, $arg)*);
+        check_with_both_hashers!(@hasher $name, $cases, $table, BuildIdentityHasher::default() $(, $arg)*);
+    }};
+    (@hasher $name:literal, $cases:expr, $table:ident, $hasher:expr $(, $arg:expr)*) => {{
+        check_against_std($name, $cases, || $table::with_hasher(4, $($arg,)* $hasher));
+        check_against_std(concat!("configured ", $name), $cases, || {
+            let mut hash_table = $table::with_hasher(4, $($arg,)* $hasher);
+            hash_table.set_growth_factor(1.5);
+            hash_table.set_max_load_factor(1.0);
+            hash_table.set_min_load_factor(Some(0.25));
+            hash_table
+        });
+    }};
+}
+
+// Checks a table wrapped so that it resizes incrementally, one bucket per operation, so that nearly every
+// sequence changes the table in the middle of a migration.
+macro_rules! check_incrementally {
+    ($name:literal, $cases:expr, $table:ident) => {{
+        check_against_std(concat!("incremental ", $name), $cases, || {
+            let mut hash_table = IncrementalHashTable::new($table::with_hasher(4, DeterministicState::default()));
+            hash_table.set_buckets_per_step(1);
+            hash_table
+        });
+    }};
+}
+
+fn check_every_table(cases: u64) {
+    check_with_both_hashers!("lpts", cases, LinearProbingTombstoneHashTable);
+    check_with_both_hashers!("qpts", cases, QuadraticProbingTombstoneHashTable);
+    check_with_both_hashers!("dhts", cases, DoubleHashingTombstoneHashTable);
+    check_with_both_hashers!("lprh", cases, LinearProbingRobinHoodHashTable);
+    check_with_both_hashers!("cuckoo", cases, CuckooHashTable);
+    check_with_both_hashers!("bucketized cuckoo", cases, BucketizedCuckooHashTable);
+    check_with_both_hashers!("hopscotch", cases, HopscotchHashTable);
+    check_with_both_hashers!("linked list chaining", cases, LinkedListChainingHashTable);
+    check_with_both_hashers!("small vector chaining", cases, SmallVecChainingHashTable);
+    check_with_both_hashers!("swiss", cases, SwissHashTable);
+    check_incrementally!("lpts", cases, LinearProbingTombstoneHashTable);
+    check_incrementally!("qpts", cases, QuadraticProbingTombstoneHashTable);
+    check_incrementally!("dhts", cases, DoubleHashingTombstoneHashTable);
+    check_incrementally!("lprh", cases, LinearProbingRobinHoodHashTable);
+    check_incrementally!("cuckoo", cases, CuckooHashTable);
+    check_incrementally!("bucketized cuckoo", cases, BucketizedCuckooHashTable);
+    check_incrementally!("hopscotch", cases, HopscotchHashTable);
+    check_incrementally!("linked list chaining", cases, LinkedListChainingHashTable);
+    check_incrementally!("small vector chaining", cases, SmallVecChainingHashTable);
+    check_incrementally!("swiss", cases, SwissHashTable);
+}
+
+pub fn run() {
+    // The shrinker should cut a long failing sequence down to the two operations that cause the failure.
+    let insert_then_remove = |operations: &[Operation]| {
+        let insert = operations.iter().position(|operation| matches!(operation, Operation::Insert(13, _)));
+        insert.is_some_and(|insert| operations[insert..].contains(&Operation::Remove(13)))
+    };
+    let operations = (0..).map(generate_operations).find(|operations| insert_then_remove(operations)).unwrap();
+    let shrunk = shrink(operations.clone(), insert_then_remove);
+    println!("Shrunk {} operations to {:?}", operations.len(), shrunk);
+    assert!(shrunk == [Operation::Insert(13, 0), Operation::Remove(13)]);
+
+    let cases = 2000;
+    check_every_table(cases);
+    println!("All tables agreed with HashMap on {} random sequences each", cases);
+    println!("All tests passed");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tables_agree_with_std_hash_map() {
+        check_every_table(200);
+    }
+}
@@ -0,0 +1,1190 @@
+use std::hash::{BuildHasherDefault, DefaultHasher, Hash, Hasher};
+
+/// Common interface implemented by every hash table in the crate, so that collision strategies can be
+/// swapped behind one API and exercised by the same checks.
+pub trait HashTable<K: Hash + Eq, V> {
+    /// Number of live entries stored in the table.
+    fn len(&self) -> usize;
+
+    /// Number of buckets currently allocated.
+    fn capacity(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Inserts the key, replacing the value if the key is already present.
+    fn insert(&mut self, key: K, value: V) {
+        match self.entry(key) {
+            Entry::Occupied(mut entry) => {
+                entry.insert(value);
+            }
+            Entry::Vacant(entry) => {
+                entry.insert(value);
+            }
+        }
+    }
+
+    /// Probes for the key once, and returns an entry to read, update or insert its value.
+    fn entry(&mut self, key: K) -> Entry<'_, K, V>;
+
+    fn get<'a>(&'a self, key: &K) -> Option<&'a V>
+    where
+        K: 'a,
+    {
+        self.get_key_value(key).map(|(_key, value)| value)
+    }
+
+    /// Like `get`, but also returns the key stored in the table.
+    fn get_key_value(&self, key: &K) -> Option<(&K, &V)>;
+
+    fn get_mut(&mut self, key: &K) -> Option<&mut V>;
+
+    /// Mutable references to the values of several keys at once. Returns `None` if any key is missing or
+    /// appears more than once.
+    fn get_many_mut<const N: usize>(&mut self, keys: [&K; N]) -> Option<[&mut V; N]>;
+
+    /// Removes the key if it is present and returns its value. Removing a missing key is a no-op.
+    fn remove(&mut self, key: &K) -> Option<V> {
+        self.remove_entry(key).map(|(_key, value)| value)
+    }
+
+    /// Like `remove`, but also returns the key stored in the table.
+    fn remove_entry(&mut self, key: &K) -> Option<(K, V)>;
+
+    /// Keeps only the entries for which `f` returns true. `f` is called once per entry.
+    fn retain<F: FnMut(&K, &mut V) -> bool>(&mut self, f: F);
+
+    fn contains_key(&self, key: &K) -> bool {
+        self.get(key).is_some()
+    }
+
+    /// Removes every entry while keeping the current capacity.
+    fn clear(&mut self);
+
+    /// Iterates over the live entries in an unspecified order.
+    fn iter<'a>(&'a self) -> impl ExactSizeIterator<Item = (&'a K, &'a V)>
+    where
+        K: 'a,
+        V: 'a;
+
+    fn iter_mut<'a>(&'a mut self) -> impl ExactSizeIterator<Item = (&'a K, &'a mut V)>
+    where
+        K: 'a,
+        V: 'a;
+
+    fn keys<'a>(&'a self) -> impl ExactSizeIterator<Item = &'a K>
+    where
+        K: 'a,
+        V: 'a,
+    {
+        self.iter().map(|(key, _value)| key)
+    }
+
+    fn values<'a>(&'a self) -> impl ExactSizeIterator<Item = &'a V>
+    where
+        K: 'a,
+        V: 'a,
+    {
+        self.iter().map(|(_key, value)| value)
+    }
+
+    fn values_mut<'a>(&'a mut self) -> impl ExactSizeIterator<Item = &'a mut V>
+    where
+        K: 'a,
+        V: 'a,
+    {
+        self.iter_mut().map(|(_key, value)| value)
+    }
+
+    /// Moves every entry out of the table, keeping the current capacity. The table is empty once the
+    /// returned iterator is dropped, even if it was not run to the end.
+    fn drain(&mut self) -> impl ExactSizeIterator<Item = (K, V)>;
+
+    /// Shrinks the table as far as possible without going below `min_capacity` or above the maximum
+    /// load factor. Never grows the table.
+    fn shrink_to(&mut self, min_capacity: usize);
+
+    fn shrink_to_fit(&mut self) {
+        self.shrink_to(0);
+    }
+
+    /// Fraction of the buckets holding live entries.
+    fn load_factor(&self) -> f64 {
+        if self.capacity() == 0 {
+            0.0
+        } else {
+            self.len() as f64 / self.capacity() as f64
+        }
+    }
+}
+
+/// Growth factor used by every table unless configured otherwise.
+pub const DEFAULT_GROWTH_FACTOR: f64 = 2.0;
+
+/// Whether adding one more entry would push the table above its maximum load factor.
+pub(crate) fn exceeds_max_load_factor(size: usize, capacity: usize, max_load_factor: f64) -> bool {
+    (size + 1) as f64 > capacity as f64 * max_load_factor
+}
+
+/// Capacity to grow to, always at least one bucket bigger than the current one.
+pub(crate) fn grown_capacity(capacity: usize, growth_factor: f64) -> usize {
+    ((capacity as f64 * growth_factor).ceil() as usize).max(capacity + 1)
+}
+
+/// Smallest capacity (at least one bucket) that holds `size` entries without exceeding `max_load_factor`.
+pub(crate) fn min_capacity_for(size: usize, max_load_factor: f64) -> usize {
+    ((size as f64 / max_load_factor).ceil() as usize).max(1)
+}
+
+/// Whether `capacity` buckets can hold `size` entries without exceeding `max_load_factor`.
+pub(crate) fn fits_in_capacity(size: usize, capacity: usize, max_load_factor: f64) -> bool {
+    capacity > 0 && size as f64 <= capacity as f64 * max_load_factor
+}
+
+/// Capacity to shrink to once the load factor drops below `min_load_factor`. It targets the middle of the
+/// allowed range, so that the table neither grows again right after shrinking nor shrinks again right away.
+pub(crate) fn shrunk_capacity(size: usize, min_load_factor: f64, max_load_factor: f64) -> usize {
+    min_capacity_for(size, (min_load_factor + max_load_factor) / 2.0)
+}
+
+pub(crate) fn assert_valid_min_load_factor(min_load_factor: Option<f64>, max_load_factor: f64) {
+    if let Some(min_load_factor) = min_load_factor {
+        assert!(
+            min_load_factor >= 0.0 && min_load_factor < max_load_factor,
+            "Min load factor must be in [0, {}), got {}",
+            max_load_factor,
+            min_load_factor,
+        );
+    }
+}
+
+pub(crate) fn assert_valid_max_load_factor(max_load_factor: f64) {
+    assert!(
+        max_load_factor > 0.0 && max_load_factor <= 1.0,
+        "Max load factor must be in (0, 1], got {}",
+        max_load_factor,
+    );
+}
+
+pub(crate) fn assert_valid_growth_factor(growth_factor: f64) {
+    assert!(growth_factor > 1.0, "Growth factor must be greater than 1, got {}", growth_factor);
+}
+
+/// Hasher that keeps the classic `key % capacity` behaviour for the teaching demos: a single integer
+/// write hashes to itself. Anything else is folded in with a multiplier, so it works for any key type,
+/// but it offers no protection against adversarial keys.
+#[derive(Default, Clone, Copy)]
+pub struct IdentityHasher {
+    hash: u64,
+}
+
+impl Hasher for IdentityHasher {
+    fn finish(&self) -> u64 {
+        self.hash
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.write_u64(*byte as u64);
+        }
+    }
+
+    fn write_u8(&mut self, i: u8) {
+        self.write_u64(i as u64);
+    }
+
+    fn write_u16(&mut self, i: u16) {
+        self.write_u64(i as u64);
+    }
+
+    fn write_u32(&mut self, i: u32) {
+        self.write_u64(i as u64);
+    }
+
+    fn write_u64(&mut self, i: u64) {
+        self.hash = self.hash.wrapping_mul(31).wrapping_add(i);
+    }
+
+    fn write_usize(&mut self, i: usize) {
+        self.write_u64(i as u64);
+    }
+}
+
+/// `BuildHasher` for `IdentityHasher`.
+pub type BuildIdentityHasher = BuildHasherDefault<IdentityHasher>;
+
+/// SipHash with fixed keys. Unlike the default `RandomState`, the same key hashes to the same bucket
+/// on every run, which makes failures reproducible.
+pub type DeterministicState = BuildHasherDefault<DefaultHasher>;
+
+/// Finalizer from SplitMix64. Used to derive extra, well-distributed hash values from a key's hash,
+/// which matters when the hasher itself does little mixing (like `IdentityHasher`).
+pub fn mix64(hash: u64) -> u64 {
+    let mut z = hash.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+/// Length of the longest run of consecutive non-empty buckets, wrapping around the end of the table.
+/// Tombstones count as non-empty because probing has to walk over them too.
+pub(crate) fn longest_cluster<T>(table: &[Option<T>]) -> usize {
+    let Some(empty_index) = table.iter().position(|bucket| bucket.is_none()) else {
+        return table.len();
+    };
+    let mut longest = 0;
+    let mut current = 0;
+    // Start right after an empty bucket so that a cluster wrapping around the end is counted once.
+    for i in 1..=table.len() {
+        if table[(empty_index + i) % table.len()].is_some() {
+            current += 1;
+            longest = longest.max(current);
+        } else {
+            current = 0;
+        }
+    }
+    longest
+}
+
+/// Builds a table of `capacity` empty buckets without requiring the bucket type to be `Clone`.
+pub(crate) fn empty_buckets<T>(capacity: usize) -> Vec<Option<T>> {
+    std::iter::repeat_with(|| None).take(capacity).collect()
+}
+
+/// View into a single key of a table, returned by `HashTable::entry`. The table has already been probed
+/// for the key, so inserting through a vacant entry does not probe again.
+pub enum Entry<'a, K, V> {
+    Occupied(OccupiedEntry<'a, K, V>),
+    Vacant(VacantEntry<'a, K, V>),
+}
+
+impl<'a, K, V> Entry<'a, K, V> {
+    pub fn key(&self) -> &K {
+        match self {
+            Entry::Occupied(entry) => entry.key(),
+            Entry::Vacant(entry) => entry.key(),
+        }
+    }
+
+    /// Inserts `default` if the key is missing, and returns the value for the key either way.
+    pub fn or_insert(self, default: V) -> &'a mut V {
+        match self {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(default),
+        }
+    }
+
+    /// Like `or_insert`, but only builds the value if the key is missing.
+    pub fn or_insert_with<F: FnOnce() -> V>(self, default: F) -> &'a mut V {
+        match self {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(default()),
+        }
+    }
+
+    pub fn or_default(self) -> &'a mut V
+    where
+        V: Default,
+    {
+        self.or_insert_with(V::default)
+    }
+
+    /// Runs `f` on the value if the key is present.
+    pub fn and_modify<F: FnOnce(&mut V)>(mut self, f: F) -> Self {
+        if let Entry::Occupied(entry) = &mut self {
+            f(entry.get_mut());
+        }
+        self
+    }
+}
+
+pub struct OccupiedEntry<'a, K, V> {
+    key: &'a K,
+    value: &'a mut V,
+}
+
+impl<'a, K, V> OccupiedEntry<'a, K, V> {
+    pub(crate) fn new(key: &'a K, value: &'a mut V) -> Self {
+        OccupiedEntry { key, value }
+    }
+
+    pub(crate) fn from_bucket<B: Bucket<Key = K, Value = V>>(bucket: &'a mut B) -> Self {
+        let (key, value) = bucket.entry_mut().expect("occupied entry points at an empty bucket");
+        OccupiedEntry { key, value }
+    }
+
+    pub fn key(&self) -> &K {
+        self.key
+    }
+
+    pub fn get(&self) -> &V {
+        self.value
+    }
+
+    pub fn get_mut(&mut self) -> &mut V {
+        self.value
+    }
+
+    pub fn into_mut(self) -> &'a mut V {
+        self.value
+    }
+
+    /// Replaces the value, returning the old one. The key stored in the table is kept.
+    pub fn insert(&mut self, value: V) -> V {
+        std::mem::replace(self.value, value)
+    }
+}
+
+/// Implemented by every table so that a `VacantEntry` can finish an insertion at the bucket picked by
+/// the table's own probe.
+pub trait InsertAtBucket<K, V> {
+    /// Inserts a key known to be missing at `index` and returns its value where it ended up.
+    fn insert_at_bucket(&mut self, index: usize, key: K, value: V) -> &mut V;
+}
+
+pub struct VacantEntry<'a, K, V> {
+    key: K,
+    index: usize,
+    table: &'a mut dyn InsertAtBucket<K, V>,
+}
+
+impl<'a, K, V> VacantEntry<'a, K, V> {
+    pub(crate) fn new(key: K, index: usize, table: &'a mut dyn InsertAtBucket<K, V>) -> Self {
+        VacantEntry { key, index, table }
+    }
+
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
+    pub fn into_key(self) -> K {
+        self.key
+    }
+
+    pub fn insert(self, value: V) -> &'a mut V {
+        self.table.insert_at_bucket(self.index, self.key, value)
+    }
+}
+
+/// Implemented by every table so that `IncrementalHashTable` can move its entries into a resized table a
+/// few buckets at a time, instead of rehashing everything in the insertion that triggers the resize.
+pub trait MigrateBuckets<K: Hash + Eq, V>: HashTable<K, V> + Sized {
+    /// Capacity the table would resize to before inserting one more entry, or `None` if it wouldn't.
+    fn capacity_to_resize_to(&self) -> Option<usize>;
+
+    /// Smallest capacity at which a table with the same settings holds `len` entries without resizing itself.
+    fn capacity_for(&self, len: usize) -> usize;
+
+    /// An empty table with the same settings and hasher as this one, and room for `capacity` entries.
+    fn empty_with_capacity(&self, capacity: usize) -> Self;
+
+    /// Number of buckets, including any overflow space, that `take_bucket` accepts.
+    fn bucket_count(&self) -> usize;
+
+    /// Bucket holding `key`, if the table contains it.
+    fn bucket_of(&self, key: &K) -> Option<usize>;
+
+    /// Moves the entries out of bucket `index` while keeping the remaining entries findable, and never resizes
+    /// the table. Other entries may move into `index`, but never into empty buckets before it, so taking the
+    /// buckets in order empties the table.
+    fn take_bucket(&mut self, index: usize) -> impl Iterator<Item = (K, V)>;
+}
+
+/// Implemented by every table to report how its entries are laid out, for comparing collision strategies.
+pub trait HashTableStats {
+    fn stats(&self) -> Stats;
+}
+
+/// Snapshot of a table's layout, returned by `HashTableStats::stats`. A probe is one bucket inspected by a
+/// lookup: a slot for the cuckoo tables, a group of control bytes for the Swiss table and a chain node for
+/// separate chaining.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Stats {
+    pub len: usize,
+    pub capacity: usize,
+    /// Probes needed to find each entry of the table.
+    pub hits: ProbeLengths,
+    /// Probes needed to tell that a key is missing, simulated once per bucket of the table.
+    pub misses: ProbeLengths,
+    pub tombstones: usize,
+    /// Longest run of consecutive non-empty buckets, or the longest chain for separate chaining.
+    pub longest_cluster: usize,
+    /// Number of entries at each offset from their preferred bucket, for the Robin Hood table.
+    pub robin_hood_offsets: Option<Vec<usize>>,
+    /// Number of insertions that displaced each number of entries, for the cuckoo tables.
+    pub cuckoo_displacements: Option<Vec<usize>>,
+}
+
+impl Stats {
+    pub fn new(len: usize, capacity: usize, hits: ProbeLengths, misses: ProbeLengths) -> Self {
+        Stats {
+            len,
+            capacity,
+            hits,
+            misses,
+            tombstones: 0,
+            longest_cluster: 0,
+            robin_hood_offsets: None,
+            cuckoo_displacements: None,
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        fn optional_histogram(histogram: &Option<Vec<usize>>) -> String {
+            histogram.as_deref().map_or_else(|| "null".to_string(), histogram_to_json)
+        }
+        format!(
+            "{{\"len\":{},\"capacity\":{},\"hits\":{},\"misses\":{},\"tombstones\":{},\"longest_cluster\":{},\
+             \"robin_hood_offsets\":{},\"cuckoo_displacements\":{}}}",
+            self.len,
+            self.capacity,
+            self.hits.to_json(),
+            self.misses.to_json(),
+            self.tombstones,
+            self.longest_cluster,
+            optional_histogram(&self.robin_hood_offsets),
+            optional_histogram(&self.cuckoo_displacements),
+        )
+    }
+}
+
+/// Distribution of the number of probes taken by a set of lookups.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ProbeLengths {
+    pub count: usize,
+    pub max: usize,
+    /// Number of lookups that took each number of probes.
+    pub histogram: Vec<usize>,
+    total: usize,
+}
+
+impl ProbeLengths {
+    pub fn add(&mut self, probes: usize) {
+        self.count += 1;
+        self.total += probes;
+        self.max = self.max.max(probes);
+        add_to_histogram(&mut self.histogram, probes);
+    }
+
+    pub fn average(&self) -> f64 {
+        if self.count == 0 { 0.0 } else { self.total as f64 / self.count as f64 }
+    }
+
+    fn to_json(&self) -> String {
+        format!(
+            "{{\"count\":{},\"average\":{},\"max\":{},\"histogram\":{}}}",
+            self.count,
+            self.average(),
+            self.max,
+            histogram_to_json(&self.histogram)
+        )
+    }
+}
+
+impl FromIterator<usize> for ProbeLengths {
+    fn from_iter<I: IntoIterator<Item = usize>>(iter: I) -> Self {
+        let mut probe_lengths = ProbeLengths::default();
+        for probes in iter {
+            probe_lengths.add(probes);
+        }
+        probe_lengths
+    }
+}
+
+pub(crate) fn add_to_histogram(histogram: &mut Vec<usize>, value: usize) {
+    if histogram.len() <= value {
+        histogram.resize(value + 1, 0);
+    }
+    histogram[value] += 1;
+}
+
+fn histogram_to_json(histogram: &[usize]) -> String {
+    let counts: Vec<String> = histogram.iter().map(usize::to_string).collect();
+    format!("[{}]", counts.join(","))
+}
+
+/// Stand-ins for the hashes of `count` keys that aren't in a table, for simulating misses in tables whose
+/// probe sequence depends on more than the preferred bucket.
+pub(crate) fn sample_hashes(count: usize) -> impl Iterator<Item = u64> {
+    (0..count as u64).map(|i| mix64(i ^ 0x5EED))
+}
+
+/// A single bucket of an open addressing table. Lets the iterators below skip empty buckets and
+/// tombstones without knowing how each table lays out its buckets.
+pub trait Bucket: Default {
+    type Key;
+    type Value;
+
+    fn entry(&self) -> Option<(&Self::Key, &Self::Value)>;
+
+    fn entry_mut(&mut self) -> Option<(&Self::Key, &mut Self::Value)>;
+
+    fn into_entry(self) -> Option<(Self::Key, Self::Value)>;
+}
+
+/// Buckets of the tombstone tables: `None` is empty and `Some(None)` is a tombstone.
+impl<K, V> Bucket for Option<Option<(K, V)>> {
+    type Key = K;
+    type Value = V;
+
+    fn entry(&self) -> Option<(&K, &V)> {
+        self.as_ref()?.as_ref().map(|(key, value)| (key, value))
+    }
+
+    fn entry_mut(&mut self) -> Option<(&K, &mut V)> {
+        self.as_mut()?.as_mut().map(|(key, value)| (&*key, value))
+    }
+
+    fn into_entry(self) -> Option<(K, V)> {
+        self.flatten()
+    }
+}
+
+/// Buckets of the cuckoo, hopscotch and Swiss tables.
+impl<K, V> Bucket for Option<(K, V)> {
+    type Key = K;
+    type Value = V;
+
+    fn entry(&self) -> Option<(&K, &V)> {
+        self.as_ref().map(|(key, value)| (key, value))
+    }
+
+    fn entry_mut(&mut self) -> Option<(&K, &mut V)> {
+        self.as_mut().map(|(key, value)| (&*key, value))
+    }
+
+    fn into_entry(self) -> Option<(K, V)> {
+        self
+    }
+}
+
+/// Buckets of the Robin Hood table, which keep each entry's offset from its preferred bucket.
+impl<K, V> Bucket for Option<(K, usize, V)> {
+    type Key = K;
+    type Value = V;
+
+    fn entry(&self) -> Option<(&K, &V)> {
+        self.as_ref().map(|(key, _offset, value)| (key, value))
+    }
+
+    fn entry_mut(&mut self) -> Option<(&K, &mut V)> {
+        self.as_mut().map(|(key, _offset, value)| (&*key, value))
+    }
+
+    fn into_entry(self) -> Option<(K, V)> {
+        self.map(|(key, _offset, value)| (key, value))
+    }
+}
+
+/// Mutable references to the values in the buckets at `indices`, which must all hold entries. Returns `None`
+/// if the same bucket appears twice.
+pub(crate) fn values_at_disjoint_buckets<B: Bucket, const N: usize>(
+    buckets: &mut [B],
+    indices: [usize; N],
+) -> Option<[&mut B::Value; N]> {
+    let buckets = buckets.get_disjoint_mut(indices).ok()?;
+    Some(buckets.map(|bucket| bucket.entry_mut().expect("index points at an empty bucket").1))
+}
+
+/// Iterator over the live entries of a table, in bucket order.
+pub struct Iter<'a, B> {
+    buckets: std::slice::Iter<'a, B>,
+    remaining: usize,
+}
+
+impl<'a, B> Iter<'a, B> {
+    /// `len` must be the number of live entries in `buckets`.
+    pub(crate) fn new(buckets: &'a [B], len: usize) -> Self {
+        Iter { buckets: buckets.iter(), remaining: len }
+    }
+}
+
+impl<'a, B: Bucket> Iterator for Iter<'a, B> {
+    type Item = (&'a B::Key, &'a B::Value);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let entry = self.buckets.find_map(Bucket::entry)?;
+        self.remaining -= 1;
+        Some(entry)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<B: Bucket> ExactSizeIterator for Iter<'_, B> {}
+
+/// Iterator over the live entries of a table with mutable access to the values.
+pub struct IterMut<'a, B> {
+    buckets: std::slice::IterMut<'a, B>,
+    remaining: usize,
+}
+
+impl<'a, B> IterMut<'a, B> {
+    /// `len` must be the number of live entries in `buckets`.
+    pub(crate) fn new(buckets: &'a mut [B], len: usize) -> Self {
+        IterMut { buckets: buckets.iter_mut(), remaining: len }
+    }
+}
+
+impl<'a, B: Bucket> Iterator for IterMut<'a, B> {
+    type Item = (&'a B::Key, &'a mut B::Value);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let entry = self.buckets.find_map(Bucket::entry_mut)?;
+        self.remaining -= 1;
+        Some(entry)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<B: Bucket> ExactSizeIterator for IterMut<'_, B> {}
+
+/// Iterator that moves the live entries out of a table it consumed.
+pub struct IntoIter<B> {
+    buckets: std::vec::IntoIter<B>,
+    remaining: usize,
+}
+
+impl<B> IntoIter<B> {
+    /// `len` must be the number of live entries in `buckets`.
+    pub(crate) fn new(buckets: Vec<B>, len: usize) -> Self {
+        IntoIter { buckets: buckets.into_iter(), remaining: len }
+    }
+}
+
+impl<B: Bucket> Iterator for IntoIter<B> {
+    type Item = (B::Key, B::Value);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let entry = self.buckets.find_map(Bucket::into_entry)?;
+        self.remaining -= 1;
+        Some(entry)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<B: Bucket> ExactSizeIterator for IntoIter<B> {}
+
+/// Iterator that moves the live entries out of a table while leaving its buckets allocated. Every
+/// bucket is emptied, including tombstones, even if the iterator is dropped before the end.
+pub struct Drain<'a, B: Bucket> {
+    buckets: std::slice::IterMut<'a, B>,
+    remaining: usize,
+}
+
+impl<'a, B: Bucket> Drain<'a, B> {
+    /// `len` must be the number of live entries in `buckets`. The table is expected to reset its own
+    /// counters, since it cannot be observed again until the drain is dropped.
+    pub(crate) fn new(buckets: &'a mut [B], len: usize) -> Self {
+        Drain { buckets: buckets.iter_mut(), remaining: len }
+    }
+}
+
+impl<B: Bucket> Iterator for Drain<'_, B> {
+    type Item = (B::Key, B::Value);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let entry = self.buckets.find_map(|bucket| std::mem::take(bucket).into_entry())?;
+        self.remaining -= 1;
+        Some(entry)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<B: Bucket> ExactSizeIterator for Drain<'_, B> {}
+
+impl<B: Bucket> Drop for Drain<'_, B> {
+    fn drop(&mut self) {
+        for bucket in &mut self.buckets {
+            *bucket = B::default();
+        }
+    }
+}
+
+#[cfg(test)]
+fn value_for(key: usize) -> String {
+    format!("value_{}", key)
+}
+
+/// Runs the same set of checks against any `HashTable` implementation.
+/// The table passed in is expected to be empty.
+#[cfg(test)]
+pub(crate) fn check_hash_table<T: HashTable<usize, String>>(hash_table: &mut T) {
+    assert!(hash_table.is_empty());
+    assert!(hash_table.len() == 0, "Size: {}", hash_table.len());
+
+    for key in 0..50 {
+        hash_table.insert(key, value_for(key));
+    }
+    assert!(hash_table.len() == 50, "Size: {}", hash_table.len());
+    assert!(hash_table.capacity() >= 50, "Capacity: {}", hash_table.capacity());
+    for key in 0..50 {
+        assert!(hash_table.get(&key) == Some(&value_for(key)), "Wrong value for key {}", key);
+    }
+    assert!(!hash_table.contains_key(&50));
+
+    // Overwriting a key must not change the size.
+    hash_table.insert(7, value_for(700));
+    assert!(hash_table.len() == 50, "Size: {}", hash_table.len());
+    assert!(hash_table.get(&7) == Some(&value_for(700)));
+
+    hash_table.get_mut(&8).unwrap().push('!');
+    assert!(hash_table.get(&8).unwrap().ends_with('!'));
+    assert!(hash_table.get_mut(&50).is_none());
+
+    for key in (0..50).step_by(2) {
+        hash_table.remove(&key);
+    }
+    hash_table.remove(&50);
+    assert!(hash_table.len() == 25, "Size: {}", hash_table.len());
+    for key in 0..50 {
+        assert!(hash_table.contains_key(&key) == (key % 2 == 1), "Wrong membership for key {}", key);
+    }
+
+    let capacity = hash_table.capacity();
+    hash_table.clear();
+    assert!(hash_table.is_empty());
+    assert!(hash_table.capacity() == capacity);
+    for key in 0..50 {
+        assert!(!hash_table.contains_key(&key));
+    }
+    hash_table.insert(3, value_for(3));
+    assert!(hash_table.get(&3) == Some(&value_for(3)));
+}
+
+/// Same idea as `check_hash_table`, but with heap-allocated keys and byte buffer values.
+#[cfg(test)]
+pub(crate) fn check_hash_table_with_string_keys<T: HashTable<String, Vec<u8>>>(hash_table: &mut T) {
+    let words = ["apple", "banana", "cherry", "date", "elderberry", "fig", "grape", "honeydew"];
+    for word in words {
+        hash_table.insert(word.to_string(), word.as_bytes().to_vec());
+    }
+    assert!(hash_table.len() == words.len(), "Size: {}", hash_table.len());
+    for word in words {
+        assert!(hash_table.get(&word.to_string()) == Some(&word.as_bytes().to_vec()));
+    }
+    assert!(!hash_table.contains_key(&"kiwi".to_string()));
+
+    hash_table.remove(&"banana".to_string());
+    assert!(!hash_table.contains_key(&"banana".to_string()));
+    assert!(hash_table.len() == words.len() - 1, "Size: {}", hash_table.len());
+}
+
+/// Checks that the table never goes above `max_load_factor` and grows by `growth_factor` when it resizes.
+#[cfg(test)]
+pub(crate) fn check_load_factor<T: HashTable<usize, usize>>(
+    hash_table: &mut T,
+    max_load_factor: f64,
+    growth_factor: f64,
+) {
+    let mut capacity = hash_table.capacity();
+    for key in 0..1000 {
+        hash_table.insert(key, key);
+        assert!(
+            hash_table.load_factor() <= max_load_factor,
+            "Load factor {} above {}",
+            hash_table.load_factor(),
+            max_load_factor,
+        );
+        if hash_table.capacity() != capacity {
+            assert!(
+                hash_table.capacity() >= grown_capacity(capacity, growth_factor),
+                "Grew from {} to {}",
+                capacity,
+                hash_table.capacity(),
+            );
+            capacity = hash_table.capacity();
+        }
+    }
+    for key in 0..1000 {
+        assert!(hash_table.get(&key) == Some(&key));
+    }
+}
+
+/// Checks that explicit shrinking gives memory back without losing entries.
+#[cfg(test)]
+pub(crate) fn check_shrinking<T: HashTable<usize, usize>>(hash_table: &mut T) {
+    for key in 0..1000 {
+        hash_table.insert(key, key);
+    }
+    let full_capacity = hash_table.capacity();
+    for key in 100..1000 {
+        hash_table.remove(&key);
+    }
+    assert!(hash_table.capacity() == full_capacity, "Capacity: {}", hash_table.capacity());
+
+    hash_table.shrink_to(500);
+    assert!(hash_table.capacity() >= 500, "Capacity: {}", hash_table.capacity());
+    assert!(hash_table.capacity() < full_capacity, "Capacity: {}", hash_table.capacity());
+
+    hash_table.shrink_to_fit();
+    assert!(hash_table.capacity() < 500, "Capacity: {}", hash_table.capacity());
+    assert!(hash_table.len() == 100, "Size: {}", hash_table.len());
+    for key in 0..1000 {
+        assert!(hash_table.get(&key) == if key < 100 { Some(&key) } else { None });
+    }
+
+    // Shrinking never grows the table.
+    let capacity = hash_table.capacity();
+    hash_table.shrink_to(10 * capacity);
+    assert!(hash_table.capacity() == capacity, "Capacity: {}", hash_table.capacity());
+
+    hash_table.clear();
+    hash_table.shrink_to_fit();
+    assert!(hash_table.capacity() >= 1, "Capacity: {}", hash_table.capacity());
+    hash_table.insert(1, 1);
+    assert!(hash_table.get(&1) == Some(&1));
+}
+
+/// Checks a table configured with a minimum load factor shrinks by itself as entries are removed.
+#[cfg(test)]
+pub(crate) fn check_automatic_shrinking<T: HashTable<usize, usize>>(hash_table: &mut T) {
+    for key in 0..1000 {
+        hash_table.insert(key, key);
+    }
+    let full_capacity = hash_table.capacity();
+    for key in 10..1000 {
+        hash_table.remove(&key);
+    }
+    assert!(hash_table.capacity() < full_capacity / 10, "Capacity: {}", hash_table.capacity());
+    for key in 0..10 {
+        assert!(hash_table.get(&key) == Some(&key));
+    }
+}
+
+/// Checks that the iterators visit every live entry exactly once, skipping empty buckets and the
+/// tombstones left by removals.
+#[cfg(test)]
+pub(crate) fn check_iterators<T>(mut hash_table: T)
+where
+    T: HashTable<usize, usize> + IntoIterator<Item = (usize, usize)>,
+    for<'a> &'a T: IntoIterator<Item = (&'a usize, &'a usize)>,
+{
+    for key in 0..100 {
+        hash_table.insert(key, key * 10);
+    }
+    for key in (0..100).step_by(3) {
+        hash_table.remove(&key);
+    }
+    let expected_keys: Vec<usize> = (0..100).filter(|key| key % 3 != 0).collect();
+
+    assert!(hash_table.iter().len() == expected_keys.len(), "Length: {}", hash_table.iter().len());
+    let mut keys: Vec<usize> = hash_table.keys().copied().collect();
+    keys.sort();
+    assert!(keys == expected_keys, "Keys: {:?}", keys);
+    for (key, value) in hash_table.iter() {
+        assert!(*value == key * 10, "Wrong value {} for key {}", value, key);
+    }
+    assert!(hash_table.values().sum::<usize>() == expected_keys.iter().map(|key| key * 10).sum::<usize>());
+
+    for (key, value) in hash_table.iter_mut() {
+        *value += key;
+    }
+    for value in hash_table.values_mut() {
+        *value += 1;
+    }
+    let mut visited = 0;
+    for (key, value) in &hash_table {
+        assert!(*value == key * 11 + 1, "Wrong value {} for key {}", value, key);
+        visited += 1;
+    }
+    assert!(visited == expected_keys.len());
+
+    // Dropping a drain early still empties the table, but keeps its capacity.
+    let capacity = hash_table.capacity();
+    let drained: Vec<(usize, usize)> = hash_table.drain().take(5).collect();
+    assert!(drained.len() == 5);
+    assert!(hash_table.is_empty());
+    assert!(hash_table.capacity() == capacity, "Capacity: {}", hash_table.capacity());
+    assert!(hash_table.iter().next().is_none());
+    for key in 0..100 {
+        assert!(!hash_table.contains_key(&key));
+    }
+
+    for key in 0..20 {
+        hash_table.insert(key, key);
+    }
+    let mut drained: Vec<(usize, usize)> = hash_table.drain().collect();
+    drained.sort();
+    assert!(drained == (0..20).map(|key| (key, key)).collect::<Vec<_>>());
+
+    for key in 0..20 {
+        hash_table.insert(key, key);
+    }
+    hash_table.remove(&7);
+    let mut entries: Vec<(usize, usize)> = hash_table.into_iter().collect();
+    entries.sort();
+    assert!(entries == (0..20).filter(|key| *key != 7).map(|key| (key, key)).collect::<Vec<_>>());
+}
+
+/// Checks the entry API by counting words, which takes a single probe per word.
+#[cfg(test)]
+pub(crate) fn check_entry<T: HashTable<String, usize>>(hash_table: &mut T) {
+    let count = |hash_table: &T, word: &str| hash_table.get(&word.to_string()).copied();
+
+    let text = "the quick brown fox jumps over the lazy dog the end";
+    for word in text.split(' ') {
+        *hash_table.entry(word.to_string()).or_insert(0) += 1;
+    }
+    assert!(hash_table.len() == 9, "Size: {}", hash_table.len());
+    assert!(count(hash_table, "the") == Some(3));
+    assert!(count(hash_table, "fox") == Some(1));
+
+    hash_table.entry("fox".to_string()).and_modify(|count| *count += 10).or_insert(100);
+    assert!(count(hash_table, "fox") == Some(11));
+    hash_table.entry("cat".to_string()).and_modify(|count| *count += 10).or_insert(100);
+    assert!(count(hash_table, "cat") == Some(100));
+
+    let mut built = false;
+    hash_table.entry("dog".to_string()).or_insert_with(|| {
+        built = true;
+        0
+    });
+    assert!(!built);
+    *hash_table.entry("emu".to_string()).or_default() += 5;
+    assert!(count(hash_table, "emu") == Some(5));
+
+    match hash_table.entry("the".to_string()) {
+        Entry::Occupied(mut entry) => {
+            assert!(entry.key() == "the");
+            assert!(*entry.get() == 3);
+            *entry.get_mut() += 1;
+            assert!(entry.insert(7) == 4);
+            *entry.into_mut() += 1;
+        }
+        Entry::Vacant(_) => panic!("\"the\" should be in the table"),
+    }
+    assert!(count(hash_table, "the") == Some(8));
+
+    // A vacant entry that is dropped without inserting leaves the table untouched.
+    match hash_table.entry("owl".to_string()) {
+        Entry::Vacant(entry) => {
+            assert!(entry.key() == "owl");
+            assert!(entry.into_key() == "owl");
+        }
+        Entry::Occupied(_) => panic!("\"owl\" should not be in the table"),
+    }
+    assert!(hash_table.entry("owl".to_string()).key() == "owl");
+    assert!(!hash_table.contains_key(&"owl".to_string()));
+    assert!(hash_table.len() == 11, "Size: {}", hash_table.len());
+
+    // Enough vacant insertions to make the table grow.
+    for i in 0..1000 {
+        *hash_table.entry(format!("word_{}", i % 250)).or_insert(0) += 1;
+    }
+    assert!(hash_table.len() == 261, "Size: {}", hash_table.len());
+    for i in 0..250 {
+        assert!(count(hash_table, &format!("word_{}", i)) == Some(4));
+    }
+}
+
+/// Checks the lookups that go beyond `get`: several shared lookups alive at once, `get_key_value` and
+/// `get_many_mut`.
+#[cfg(test)]
+pub(crate) fn check_lookups<T: HashTable<usize, String>>(hash_table: &mut T) {
+    for key in 0..20 {
+        hash_table.insert(key, value_for(key));
+    }
+
+    let shared: &T = hash_table;
+    let (first, second) = (shared.get(&1), shared.get(&2));
+    assert!(first == Some(&value_for(1)) && second == Some(&value_for(2)));
+    assert!(shared.get_key_value(&3) == Some((&3, &value_for(3))));
+    assert!(shared.get_key_value(&20).is_none());
+
+    let [first, second, third] = hash_table.get_many_mut([&4, &5, &6]).expect("all three keys are present");
+    std::mem::swap(first, second);
+    third.push('!');
+    assert!(hash_table.get(&4) == Some(&value_for(5)));
+    assert!(hash_table.get(&5) == Some(&value_for(4)));
+    assert!(hash_table.get(&6) == Some(&format!("{}!", value_for(6))));
+
+    assert!(hash_table.get_many_mut([&7, &7]).is_none());
+    assert!(hash_table.get_many_mut([&7, &20]).is_none());
+    assert!(hash_table.get_many_mut([]).is_some());
+}
+
+/// Checks that removals move keys and values out of the table, and that `retain` visits every entry once.
+#[cfg(test)]
+pub(crate) fn check_removal<T: HashTable<usize, String>>(hash_table: &mut T) {
+    for key in 0..100 {
+        hash_table.insert(key, value_for(key));
+    }
+    assert!(hash_table.remove(&10) == Some(value_for(10)));
+    assert!(hash_table.remove(&10).is_none());
+    assert!(hash_table.remove_entry(&11) == Some((11, value_for(11))));
+    assert!(hash_table.remove_entry(&11).is_none());
+    assert!(hash_table.len() == 98, "Size: {}", hash_table.len());
+
+    let mut calls = 0;
+    hash_table.retain(|key, value| {
+        calls += 1;
+        value.push('!');
+        key % 3 != 0
+    });
+    assert!(calls == 98, "retain called f {} times", calls);
+    for key in 0..100 {
+        let kept = key % 3 != 0 && key != 10 && key != 11;
+        assert!(hash_table.contains_key(&key) == kept, "Wrong membership for key {}", key);
+        if kept {
+            assert!(hash_table.get(&key) == Some(&format!("{}!", value_for(key))));
+        }
+    }
+    assert!(hash_table.len() == 64, "Size: {}", hash_table.len());
+
+    hash_table.retain(|_, _| false);
+    assert!(hash_table.is_empty());
+    hash_table.insert(1, value_for(1));
+    assert!(hash_table.get(&1) == Some(&value_for(1)));
+}
+
+#[cfg(test)]
+pub(crate) fn check_stats<T: HashTable<usize, usize> + HashTableStats>(hash_table: &mut T) {
+    let stats = hash_table.stats();
+    assert!(stats.len == 0 && stats.hits.count == 0 && stats.hits.average() == 0.0);
+    assert!(stats.misses.count > 0);
+
+    for key in 0..200 {
+        hash_table.insert(key, key);
+    }
+    for key in (0..200).step_by(4) {
+        hash_table.remove(&key);
+    }
+    let stats = hash_table.stats();
+    assert!(stats.len == 150 && stats.capacity == hash_table.capacity());
+    // Every entry is found, and every lookup inspects at least one bucket.
+    assert!(stats.hits.count == 150, "Hits: {}", stats.hits.count);
+    assert!(stats.hits.histogram.iter().sum::<usize>() == 150);
+    assert!(stats.hits.histogram.first().is_none_or(|&count| count == 0));
+    assert!(stats.hits.max == stats.hits.histogram.len() - 1);
+    assert!(stats.hits.average() >= 1.0 && stats.hits.average() <= stats.hits.max as f64);
+    assert!(stats.misses.histogram.iter().sum::<usize>() == stats.misses.count);
+    assert!(stats.misses.average() <= stats.misses.max as f64);
+    assert!(stats.longest_cluster > 0);
+    if let Some(offsets) = &stats.robin_hood_offsets {
+        assert!(offsets.iter().sum::<usize>() == 150);
+        assert!(offsets.len() == stats.hits.histogram.len() - 1, "Hits are found one probe past their offset");
+    }
+    if let Some(displacements) = &stats.cuckoo_displacements {
+        assert!(displacements.iter().sum::<usize>() >= 200);
+    }
+
+    let json = stats.to_json();
+    assert!(json.starts_with(&format!("{{\"len\":150,\"capacity\":{},\"hits\":{{\"count\":150,", stats.capacity)));
+    assert!(json.matches('{').count() == 3 && json.matches('}').count() == 3);
+    assert!(json.matches('[').count() == json.matches(']').count());
+}
+
+/// Expands to a `#[test]` for each of the checks above, run against `$table`s with a capacity of 10 and the
+/// module's default max load factor.
+#[cfg(test)]
+macro_rules! hash_table_tests {
+    ($table:ident) => {
+        #[test]
+        fn basic_operations() {
+            $crate::hash_table::check_hash_table(&mut $table::new(10));
+        }
+
+        #[test]
+        fn string_keys() {
+            let mut hash_table = $table::with_hasher(10, $crate::hash_table::DeterministicState::default());
+            $crate::hash_table::check_hash_table_with_string_keys(&mut hash_table);
+        }
+
+        #[test]
+        fn load_factor() {
+            let mut hash_table = $table::new(10);
+            let growth_factor = $crate::hash_table::DEFAULT_GROWTH_FACTOR;
+            $crate::hash_table::check_load_factor(&mut hash_table, DEFAULT_MAX_LOAD_FACTOR, growth_factor);
+            let mut hash_table = $table::new(10);
+            hash_table.set_max_load_factor(0.6);
+            hash_table.set_growth_factor(1.5);
+            $crate::hash_table::check_load_factor(&mut hash_table, 0.6, 1.5);
+        }
+
+        #[test]
+        fn shrinking() {
+            $crate::hash_table::check_shrinking(&mut $table::new(10));
+        }
+
+        #[test]
+        fn automatic_shrinking() {
+            let mut hash_table = $table::new(10);
+            hash_table.set_min_load_factor(Some(0.2));
+            $crate::hash_table::check_automatic_shrinking(&mut hash_table);
+        }
+
+        #[test]
+        fn iterators() {
+            $crate::hash_table::check_iterators($table::new(10));
+        }
+
+        #[test]
+        fn entry() {
+            $crate::hash_table::check_entry(&mut $table::new(10));
+        }
+
+        #[test]
+        fn lookups() {
+            $crate::hash_table::check_lookups(&mut $table::new(10));
+        }
+
+        #[test]
+        fn removal() {
+            $crate::hash_table::check_removal(&mut $table::new(10));
+        }
+
+        #[test]
+        fn stats() {
+            $crate::hash_table::check_stats(&mut $table::new(10));
+        }
+    };
+}
+
+#[cfg(test)]
+pub(crate) use hash_table_tests;
+
+/// Checks that churning through thousands of short-lived keys next to a few long-lived ones doesn't grow a
+/// tombstone table forever, and that `compact` clears the tombstones without resizing.
+#[cfg(test)]
+pub(crate) fn check_compaction<T: HashTable<usize, usize>>(
+    hash_table: &mut T,
+    tombstone_count: impl Fn(&T) -> usize,
+    compact: impl Fn(&mut T),
+) {
+    for key in 0..50 {
+        hash_table.insert(key, key);
+    }
+    let mut settled_capacity = 0;
+    for key in 50..5000 {
+        hash_table.insert(key, key);
+        hash_table.remove(&key);
+        assert!(hash_table.len() + tombstone_count(hash_table) <= hash_table.capacity());
+        if key == 1000 {
+            settled_capacity = hash_table.capacity();
+        } else if key > 1000 {
+            assert!(hash_table.capacity() == settled_capacity, "Capacity: {}", hash_table.capacity());
+        }
+    }
+    for key in 0..50 {
+        assert!(hash_table.get(&key) == Some(&key));
+    }
+    compact(hash_table);
+    assert!(tombstone_count(hash_table) == 0);
+    assert!(hash_table.capacity() == settled_capacity, "Capacity: {}", hash_table.capacity());
+    for key in 0..50 {
+        assert!(hash_table.get(&key) == Some(&key));
+    }
+}
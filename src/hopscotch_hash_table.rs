@@ -0,0 +1,633 @@
+use std::collections::hash_map::RandomState;
+use std::fmt::Debug;
+use std::hash::{BuildHasher, Hash};
+use std::ops::Range;
+
+use crate::hash_table::{
+    self, BuildIdentityHasher, Entry, HashTable, HashTableStats, InsertAtBucket, MigrateBuckets, OccupiedEntry, Stats,
+    VacantEntry,
+};
+
+// Every key lives within this many buckets of its home bucket.
+const DEFAULT_NEIGHBORHOOD: usize = 32;
+
+// The neighborhood bitmap of each bucket is a u64.
+const MAX_NEIGHBORHOOD: usize = 64;
+
+// Hopping keeps every key close to its home bucket even when the table is nearly full.
+const DEFAULT_MAX_LOAD_FACTOR: f64 = 0.9;
+
+pub struct HopscotchHashTable<K, V, S = RandomState> {
+    // The `capacity` buckets, followed by the slots of the stash. Keys whose neighborhood can't be freed, which
+    // happens when too many of them share a home bucket, overflow into the stash.
+    table: Vec<Option<(K, V)>>,
+    // Bit i of `hop_info[b]` is set when the entry in bucket `(b + i) % capacity` has `b` as its home bucket.
+    hop_info: Vec<u64>,
+    capacity: usize,
+    size: usize,
+    hash_builder: S,
+    max_load_factor: f64,
+    growth_factor: f64,
+    // When set, removals shrink the table once the load factor drops below this value.
+    min_load_factor: Option<f64>,
+    neighborhood: usize,
+}
+
+impl<K: Hash + Eq, V> HopscotchHashTable<K, V> {
+    pub fn new(capacity: usize) -> Self {
+        Self::with_hasher(capacity, RandomState::new())
+    }
+}
+
+impl<K: Hash + Eq, V, S: BuildHasher> HopscotchHashTable<K, V, S> {
+    /// The capacity is at least one bucket, since lookups hash into the table before anything grows it.
+    pub fn with_hasher(capacity: usize, hash_builder: S) -> Self {
+        let capacity = capacity.max(1);
+        HopscotchHashTable {
+            table: hash_table::empty_buckets(capacity),
+            hop_info: vec![0; capacity],
+            capacity,
+            size: 0,
+            hash_builder,
+            max_load_factor: DEFAULT_MAX_LOAD_FACTOR,
+            growth_factor: hash_table::DEFAULT_GROWTH_FACTOR,
+            min_load_factor: None,
+            neighborhood: DEFAULT_NEIGHBORHOOD,
+        }
+    }
+
+    /// The table grows before an insertion would take it above this fraction of its capacity.
+    pub fn set_max_load_factor(&mut self, max_load_factor: f64) {
+        hash_table::assert_valid_max_load_factor(max_load_factor);
+        hash_table::assert_valid_min_load_factor(self.min_load_factor, max_load_factor);
+        self.max_load_factor = max_load_factor;
+    }
+
+    /// Makes removals shrink the table once its load factor drops below `min_load_factor`.
+    /// `None`, the default, never shrinks automatically.
+    pub fn set_min_load_factor(&mut self, min_load_factor: Option<f64>) {
+        hash_table::assert_valid_min_load_factor(min_load_factor, self.max_load_factor);
+        self.min_load_factor = min_load_factor;
+    }
+
+    /// Factor the capacity is multiplied by whenever the table grows.
+    pub fn set_growth_factor(&mut self, growth_factor: f64) {
+        hash_table::assert_valid_growth_factor(growth_factor);
+        self.growth_factor = growth_factor;
+    }
+
+    /// Number of buckets, starting at its home bucket, that a key may be stored in. Smaller neighborhoods
+    /// make lookups cheaper but make the table grow sooner. Rebuilds the table if it holds entries.
+    pub fn set_neighborhood(&mut self, neighborhood: usize) {
+        assert!(
+            (1..=MAX_NEIGHBORHOOD).contains(&neighborhood),
+            "Neighborhood must be in [1, {}], got {}",
+            MAX_NEIGHBORHOOD,
+            neighborhood,
+        );
+        self.neighborhood = neighborhood;
+        if self.size > 0 {
+            assert!(self.resize_to(self.capacity));
+        }
+    }
+
+    /// Number of entries currently in the stash.
+    pub fn stash_len(&self) -> usize {
+        self.table[self.stash_slots()].iter().flatten().count()
+    }
+
+    fn get_home_index(&self, key: &K) -> usize {
+        (self.hash_builder.hash_one(key) % self.capacity as u64) as usize
+    }
+
+    // A neighborhood never wraps all the way around a small table.
+    fn effective_neighborhood(&self) -> usize {
+        self.neighborhood.min(self.capacity)
+    }
+
+    fn stash_slots(&self) -> Range<usize> {
+        self.capacity..self.table.len()
+    }
+
+    // An empty slot of the stash, added to the end of the table if every slot is taken.
+    fn empty_stash_slot(&mut self) -> usize {
+        self.stash_slots().find(|&slot| self.table[slot].is_none()).unwrap_or_else(|| {
+            self.table.push(None);
+            self.table.len() - 1
+        })
+    }
+
+    fn resize_to(&mut self, new_capacity: usize) -> bool {
+        if !hash_table::fits_in_capacity(self.size, new_capacity, self.max_load_factor) {
+            return false;
+        }
+        self.capacity = new_capacity;
+        let old_table = std::mem::replace(&mut self.table, hash_table::empty_buckets(self.capacity));
+        self.hop_info = vec![0; self.capacity];
+        self.size = 0;
+        for (key, value) in old_table.into_iter().flatten() {
+            self.insert(key, value);
+        }
+        true
+    }
+
+    // Shrinks the table if removals took it below the minimum load factor.
+    fn shrink_if_underloaded(&mut self) {
+        if let Some(min_load_factor) = self.min_load_factor
+            && (self.size as f64) < self.capacity as f64 * min_load_factor
+        {
+            self.shrink_to(hash_table::shrunk_capacity(self.size, min_load_factor, self.max_load_factor));
+        }
+    }
+
+    fn resize(&mut self) {
+        assert!(self.resize_to(hash_table::grown_capacity(self.capacity, self.growth_factor)));
+    }
+
+    fn find_index_for_key_if_exists(&self, key: &K) -> Option<usize> {
+        self.probe(self.get_home_index(key), |existing_key| existing_key == key).0
+    }
+
+    // Checks the buckets flagged in the bitmap of `home_index`, then the stash, until `is_match` accepts a key.
+    // Returns the bucket of the accepted key, if any, and the number of buckets inspected.
+    fn probe(&self, home_index: usize, is_match: impl Fn(&K) -> bool) -> (Option<usize>, usize) {
+        // Outside the stash, only the buckets flagged in the home bucket's bitmap can hold the key.
+        let mut hops = self.hop_info[home_index];
+        let mut probes = 0;
+        while hops != 0 {
+            let index = (home_index + hops.trailing_zeros() as usize) % self.capacity;
+            probes += 1;
+            if let Some((existing_key, _)) = &self.table[index]
+                && is_match(existing_key)
+            {
+                return (Some(index), probes);
+            }
+            hops &= hops - 1;
+        }
+        for slot in self.stash_slots() {
+            if let Some((existing_key, _)) = &self.table[slot] {
+                probes += 1;
+                if is_match(existing_key) {
+                    return (Some(slot), probes);
+                }
+            }
+        }
+        (None, probes)
+    }
+
+    // Finds an empty bucket by linear probing from `home_index`, then hops it backwards until it is within the
+    // neighborhood of `home_index`. Each hop moves an entry further from its own home bucket into the empty
+    // bucket, without leaving that entry's neighborhood. Returns None if the table is full or no entry can be
+    // moved, in which case the table has to grow.
+    fn make_room_in_neighborhood(&mut self, home_index: usize) -> Option<usize> {
+        let neighborhood = self.effective_neighborhood();
+        let mut distance = (0..self.capacity).find(|distance| {
+            self.table[(home_index + distance) % self.capacity].is_none()
+        })?;
+
+        while distance >= neighborhood {
+            let empty_index = (home_index + distance) % self.capacity;
+            // Look at the buckets whose neighborhood reaches the empty bucket, furthest first, for an entry that
+            // sits before the empty bucket.
+            let hop = (1..neighborhood).rev().find_map(|back| {
+                let candidate_home = (empty_index + self.capacity - back) % self.capacity;
+                let movable = self.hop_info[candidate_home] & ((1 << back) - 1);
+                (movable != 0).then(|| (candidate_home, back, movable.trailing_zeros() as usize))
+            });
+            let (candidate_home, back, offset) = hop?;
+
+            self.table.swap((candidate_home + offset) % self.capacity, empty_index);
+            self.hop_info[candidate_home] &= !(1 << offset);
+            self.hop_info[candidate_home] |= 1 << back;
+            distance -= back - offset;
+        }
+        Some((home_index + distance) % self.capacity)
+    }
+
+    // Clears the flag of the entry for `key` in bucket `index` from its home bucket's bitmap, unless the entry
+    // is in the stash.
+    fn unflag(&mut self, key: &K, index: usize) {
+        if index < self.capacity {
+            let home_index = self.get_home_index(key);
+            let offset = (index + self.capacity - home_index) % self.capacity;
+            self.hop_info[home_index] &= !(1 << offset);
+        }
+    }
+
+    // Checks that every entry is flagged in the bitmap of its home bucket, and every flag points at an entry.
+    fn assert_neighborhoods_are_consistent(&self) {
+        let mut flagged = 0;
+        for (home_index, hops) in self.hop_info.iter().enumerate() {
+            for offset in 0..self.effective_neighborhood() {
+                if hops & (1 << offset) != 0 {
+                    let index = (home_index + offset) % self.capacity;
+                    let Some((key, _)) = &self.table[index] else {
+                        panic!("Bucket {} is flagged for home bucket {} but is empty", index, home_index);
+                    };
+                    assert!(self.get_home_index(key) == home_index, "Bucket {} has the wrong home", index);
+                    flagged += 1;
+                }
+            }
+        }
+        let stash_len = self.stash_len();
+        assert!(
+            flagged + stash_len == self.size,
+            "{} flagged and {} stashed entries, size {}",
+            flagged,
+            stash_len,
+            self.size,
+        );
+    }
+}
+
+impl<K: Debug, V: Debug, S> HopscotchHashTable<K, V, S> {
+    pub fn print(&self) {
+        println!("Capacity: {}", self.capacity);
+        println!("Size: {}", self.size);
+        println!("Entries (with the neighborhood bitmap of each bucket, nearest bucket first):");
+        let (buckets, stash) = self.table.split_at(self.capacity);
+        for (entry, hops) in buckets.iter().zip(&self.hop_info) {
+            let bitmap: String = (0..self.neighborhood.min(self.capacity))
+                .map(|offset| if hops & (1 << offset) != 0 { '1' } else { '0' })
+                .collect();
+            if let Some((key, value)) = entry {
+                println!("\t[{}] {:?}: {:?}", bitmap, key, value);
+            } else {
+                println!("\t[{}] <None>", bitmap);
+            }
+        }
+        if stash.iter().any(Option::is_some) {
+            println!("Stash:");
+            for (key, value) in stash.iter().flatten() {
+                println!("\t{:?}: {:?}", key, value);
+            }
+        }
+    }
+}
+
+impl<K: Hash + Eq, V, S: BuildHasher> HashTable<K, V> for HopscotchHashTable<K, V, S> {
+    fn len(&self) -> usize {
+        self.size
+    }
+
+    fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    fn entry(&mut self, key: K) -> Entry<'_, K, V> {
+        if let Some(index) = self.find_index_for_key_if_exists(&key) {
+            return Entry::Occupied(OccupiedEntry::from_bucket(&mut self.table[index]));
+        }
+
+        // Resize the table because it's getting too full.
+        if hash_table::exceeds_max_load_factor(self.size, self.capacity, self.max_load_factor) {
+            self.resize();
+        }
+        loop {
+            let home_index = self.get_home_index(&key);
+            if let Some(index) = self.make_room_in_neighborhood(home_index) {
+                return Entry::Vacant(VacantEntry::new(key, index, self));
+            }
+            // Growing spreads the keys over more home buckets, which frees neighborhoods in a crowded table. In
+            // a table at most half as full as allowed, the neighborhood is full because too many keys share its
+            // home bucket, and no capacity would tell them apart, so the key goes to the stash instead. Every
+            // resize lowers the load factor, so this stops growing after a few rounds.
+            if !hash_table::exceeds_max_load_factor(self.size, self.capacity, self.max_load_factor / 2.0) {
+                let slot = self.empty_stash_slot();
+                return Entry::Vacant(VacantEntry::new(key, slot, self));
+            }
+            self.resize();
+        }
+    }
+
+    fn get_key_value(&self, key: &K) -> Option<(&K, &V)> {
+        let found_index = self.find_index_for_key_if_exists(key)?;
+        if let Some((key, value)) = &self.table[found_index] {
+            Some((key, value))
+        } else {
+            None
+        }
+    }
+
+    fn get_mut(&mut self, key: &K) -> Option<&mut V> {
+        let found_index = self.find_index_for_key_if_exists(key)?;
+        if let Some((_key, value)) = &mut self.table[found_index] {
+            Some(value)
+        } else {
+            None
+        }
+    }
+
+    fn get_many_mut<const N: usize>(&mut self, keys: [&K; N]) -> Option<[&mut V; N]> {
+        let mut indices = [0; N];
+        for (index, key) in indices.iter_mut().zip(keys) {
+            *index = self.find_index_for_key_if_exists(key)?;
+        }
+        hash_table::values_at_disjoint_buckets(&mut self.table, indices)
+    }
+
+    fn remove_entry(&mut self, key: &K) -> Option<(K, V)> {
+        let found_index = self.find_index_for_key_if_exists(key)?;
+        self.unflag(key, found_index);
+        let entry = self.table[found_index].take();
+        self.size -= 1;
+        self.shrink_if_underloaded();
+        entry
+    }
+
+    fn retain<F: FnMut(&K, &mut V) -> bool>(&mut self, mut f: F) {
+        // Walk the entries through their home buckets' bitmaps, so that the bits to clear are known without
+        // hashing the keys again.
+        for home_index in 0..self.capacity {
+            let mut hops = self.hop_info[home_index];
+            while hops != 0 {
+                let offset = hops.trailing_zeros() as usize;
+                hops &= hops - 1;
+                let index = (home_index + offset) % self.capacity;
+                if let Some((key, value)) = &mut self.table[index]
+                    && !f(key, value)
+                {
+                    self.table[index] = None;
+                    self.hop_info[home_index] &= !(1 << offset);
+                    self.size -= 1;
+                }
+            }
+        }
+        for slot in self.stash_slots() {
+            if let Some((key, value)) = &mut self.table[slot]
+                && !f(key, value)
+            {
+                self.table[slot] = None;
+                self.size -= 1;
+            }
+        }
+        self.shrink_if_underloaded();
+    }
+
+    fn clear(&mut self) {
+        self.table = hash_table::empty_buckets(self.capacity);
+        self.hop_info = vec![0; self.capacity];
+        self.size = 0;
+    }
+
+    fn iter<'a>(&'a self) -> impl ExactSizeIterator<Item = (&'a K, &'a V)>
+    where
+        K: 'a,
+        V: 'a,
+    {
+        hash_table::Iter::new(&self.table, self.size)
+    }
+
+    fn iter_mut<'a>(&'a mut self) -> impl ExactSizeIterator<Item = (&'a K, &'a mut V)>
+    where
+        K: 'a,
+        V: 'a,
+    {
+        hash_table::IterMut::new(&mut self.table, self.size)
+    }
+
+    fn drain(&mut self) -> impl ExactSizeIterator<Item = (K, V)> {
+        let len = self.size;
+        self.size = 0;
+        self.hop_info.fill(0);
+        hash_table::Drain::new(&mut self.table, len)
+    }
+
+    fn shrink_to(&mut self, min_capacity: usize) {
+        let new_capacity = min_capacity.max(hash_table::min_capacity_for(self.size, self.max_load_factor));
+        if new_capacity < self.capacity {
+            assert!(self.resize_to(new_capacity));
+        }
+    }
+}
+
+impl<K: Hash + Eq, V, S: BuildHasher> InsertAtBucket<K, V> for HopscotchHashTable<K, V, S> {
+    fn insert_at_bucket(&mut self, index: usize, key: K, value: V) -> &mut V {
+        // Stashed entries aren't flagged in any bitmap.
+        if index < self.capacity {
+            let home_index = self.get_home_index(&key);
+            let offset = (index + self.capacity - home_index) % self.capacity;
+            self.hop_info[home_index] |= 1 << offset;
+        }
+        self.size += 1;
+        &mut self.table[index].insert((key, value)).1
+    }
+}
+
+impl<K: Hash + Eq, V, S: BuildHasher + Clone> MigrateBuckets<K, V> for HopscotchHashTable<K, V, S> {
+    fn capacity_to_resize_to(&self) -> Option<usize> {
+        hash_table::exceeds_max_load_factor(self.size, self.capacity, self.max_load_factor)
+            .then(|| hash_table::grown_capacity(self.capacity, self.growth_factor))
+    }
+
+    fn capacity_for(&self, len: usize) -> usize {
+        hash_table::min_capacity_for(len, self.max_load_factor)
+    }
+
+    fn empty_with_capacity(&self, capacity: usize) -> Self {
+        let mut hash_table = Self::with_hasher(capacity, self.hash_builder.clone());
+        hash_table.max_load_factor = self.max_load_factor;
+        hash_table.growth_factor = self.growth_factor;
+        hash_table.min_load_factor = self.min_load_factor;
+        hash_table.neighborhood = self.neighborhood;
+        hash_table
+    }
+
+    fn bucket_count(&self) -> usize {
+        self.table.len()
+    }
+
+    fn bucket_of(&self, key: &K) -> Option<usize> {
+        self.find_index_for_key_if_exists(key)
+    }
+
+    fn take_bucket(&mut self, index: usize) -> impl Iterator<Item = (K, V)> {
+        let entry = self.table[index].take();
+        if let Some((key, _)) = &entry {
+            self.unflag(key, index);
+            self.size -= 1;
+        }
+        entry.into_iter()
+    }
+}
+
+impl<K: Hash + Eq, V, S: BuildHasher> HashTableStats for HopscotchHashTable<K, V, S> {
+    fn stats(&self) -> Stats {
+        let hits = self.keys().map(|key| {
+            self.probe(self.get_home_index(key), |existing_key| existing_key == key).1
+        });
+        // A miss checks every entry that shares its home bucket, then the stash.
+        let misses = (0..self.capacity).map(|index| self.probe(index, |_| false).1);
+        let mut stats = Stats::new(self.size, self.capacity, hits.collect(), misses.collect());
+        stats.longest_cluster = hash_table::longest_cluster(&self.table[..self.capacity]);
+        stats
+    }
+}
+
+impl<K, V, S> IntoIterator for HopscotchHashTable<K, V, S> {
+    type Item = (K, V);
+    type IntoIter = hash_table::IntoIter<Option<(K, V)>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        hash_table::IntoIter::new(self.table, self.size)
+    }
+}
+
+impl<'a, K, V, S> IntoIterator for &'a HopscotchHashTable<K, V, S> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = hash_table::Iter<'a, Option<(K, V)>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        hash_table::Iter::new(&self.table, self.size)
+    }
+}
+
+impl<'a, K, V, S> IntoIterator for &'a mut HopscotchHashTable<K, V, S> {
+    type Item = (&'a K, &'a mut V);
+    type IntoIter = hash_table::IterMut<'a, Option<(K, V)>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        hash_table::IterMut::new(&mut self.table, self.size)
+    }
+}
+
+pub fn run() {
+    let capacity: usize = 10;
+    // The identity hasher keeps keys on `key % capacity` so the layouts below are predictable.
+    let mut hash_table = HopscotchHashTable::with_hasher(capacity, BuildIdentityHasher::default());
+    // Let the table fill up completely, and keep neighborhoods small so that hops happen early.
+    hash_table.set_max_load_factor(1.0);
+    hash_table.set_neighborhood(4);
+
+    let one: Vec<char> = "one".chars().collect();
+    let two: Vec<char> = "two".chars().collect();
+    let three: Vec<char> = "three".chars().collect();
+    let four: Vec<char> = "four".chars().collect();
+    let eleven: Vec<char> = "eleven".chars().collect();
+    let twenty_one: Vec<char> = "twenty_one".chars().collect();
+    let thirty_one: Vec<char> = "thirty_one".chars().collect();
+    let forty_one: Vec<char> = "forty_one".chars().collect();
+
+    hash_table.insert(1, one.clone());
+    hash_table.insert(2, two.clone());
+    hash_table.insert(3, three.clone());
+    hash_table.insert(4, four.clone());
+    println!("Initial:");
+    hash_table.print();
+    assert!(hash_table.size == 4, "Size: {}", hash_table.size);
+    hash_table.assert_neighborhoods_are_consistent();
+
+    // Buckets 1 to 4 are full, so the first empty bucket is 5, outside the neighborhood of bucket 1. Key 2 can
+    // hop from bucket 2 to bucket 5 without leaving its own neighborhood, which frees bucket 2 for 11.
+    hash_table.insert(11, eleven.clone());
+    println!("Inserted 11, which should make 2 hop from bucket 2 to bucket 5:");
+    hash_table.print();
+    assert!(hash_table.find_index_for_key_if_exists(&11) == Some(2));
+    assert!(hash_table.find_index_for_key_if_exists(&2) == Some(5));
+    hash_table.assert_neighborhoods_are_consistent();
+
+    hash_table.insert(21, twenty_one.clone());
+    hash_table.insert(31, thirty_one.clone());
+    println!("Inserted 21 & 31, which should make 3 and 4 hop out of the neighborhood of bucket 1:");
+    hash_table.print();
+    assert!(hash_table.find_index_for_key_if_exists(&21) == Some(3));
+    assert!(hash_table.find_index_for_key_if_exists(&31) == Some(4));
+    assert!(hash_table.find_index_for_key_if_exists(&3) == Some(6));
+    assert!(hash_table.find_index_for_key_if_exists(&4) == Some(7));
+    assert!(hash_table.size == 7, "Size: {}", hash_table.size);
+    hash_table.assert_neighborhoods_are_consistent();
+
+    assert!(hash_table.remove(&11) == Some(eleven.clone()));
+    println!("After deleting key 11:");
+    hash_table.print();
+    assert!(hash_table.get(&11).is_none());
+    assert!(hash_table.size == 6, "Size: {}", hash_table.size);
+    hash_table.assert_neighborhoods_are_consistent();
+
+    hash_table.insert(11, eleven.clone());
+    println!("Re-inserted 11, which should take bucket 2 again without any hops:");
+    hash_table.print();
+    assert!(hash_table.find_index_for_key_if_exists(&11) == Some(2));
+    hash_table.assert_neighborhoods_are_consistent();
+
+    // Lookups only check the entries flagged in the home bucket's bitmap, so 2, 3 & 4 are found at once even
+    // though they hopped, and a miss on any bucket but 1 to 4 checks nothing at all.
+    let stats = hash_table.stats();
+    println!("Stats: {}", stats.to_json());
+    assert!(stats.hits.histogram == [0, 4, 1, 1, 1]);
+    assert!(stats.misses.max == 4 && stats.misses.average() == 0.7);
+    assert!(stats.longest_cluster == 7);
+
+    // The neighborhood of bucket 1 holds 1, 11, 21 & 31, and none of the entries near the next empty bucket
+    // can hop towards it, so the table has to grow.
+    hash_table.insert(41, forty_one.clone());
+    println!("Inserted 41, table should be resized to 20:");
+    hash_table.print();
+    assert!(hash_table.capacity == 20, "Capacity: {}", hash_table.capacity);
+    assert!(hash_table.size == 8, "Size: {}", hash_table.size);
+    for (key, value) in [(1, &one), (2, &two), (3, &three), (4, &four), (11, &eleven), (41, &forty_one)] {
+        assert!(hash_table.get(&key) == Some(value), "Wrong value for key {}", key);
+    }
+    hash_table.assert_neighborhoods_are_consistent();
+
+    // A small neighborhood with random hashing still finds every key, it just grows sooner.
+    let mut hash_table = HopscotchHashTable::new(capacity);
+    hash_table.set_neighborhood(8);
+    for key in 0..1000 {
+        hash_table.insert(key, key);
+    }
+    hash_table.assert_neighborhoods_are_consistent();
+    for key in 0..1000 {
+        assert!(hash_table.get(&key) == Some(&key));
+    }
+
+    // With the identity hasher, multiples of every capacity the table grows through all have bucket 0 as their
+    // home. Only 32 of them fit in its neighborhood, and growing can't tell the rest apart, so they overflow into
+    // the stash once growing has brought the table down to half its max load factor.
+    let mut hash_table = HopscotchHashTable::with_hasher(capacity, BuildIdentityHasher::default());
+    let keys: Vec<usize> = (0..100).map(|i| (i * capacity) << 30).collect();
+    for &key in &keys {
+        hash_table.insert(key, key);
+    }
+    println!(
+        "Inserted 100 keys sharing a home bucket: capacity {}, {} in the stash",
+        hash_table.capacity,
+        hash_table.stash_len(),
+    );
+    assert!(hash_table.stash_len() == 100 - DEFAULT_NEIGHBORHOOD, "Stash: {}", hash_table.stash_len());
+    assert!(hash_table.load_factor() >= DEFAULT_MAX_LOAD_FACTOR / 4.0, "Load factor: {}", hash_table.load_factor());
+    hash_table.assert_neighborhoods_are_consistent();
+    assert!(keys.iter().all(|key| hash_table.get(key) == Some(key)));
+    // Removing keys from the neighborhood and the stash alike leaves the rest where they are.
+    hash_table.retain(|&key, _| !(key / keys[1]).is_multiple_of(3));
+    assert!(hash_table.remove(&keys[1]) == Some(keys[1]));
+    assert!(hash_table.remove(&keys[98]) == Some(keys[98]));
+    hash_table.assert_neighborhoods_are_consistent();
+    for (i, key) in keys.iter().enumerate() {
+        let expected = (i % 3 != 0 && i != 1 && i != 98).then_some(key);
+        assert!(hash_table.get(key) == expected, "Wrong value for key {}", key);
+    }
+    // Shrinking rebuilds the table at a capacity that isn't a factor of the keys, which spreads them over
+    // different home buckets and empties the stash.
+    hash_table.shrink_to_fit();
+    println!("Shrunk to capacity {}, {} in the stash", hash_table.capacity, hash_table.stash_len());
+    assert!(hash_table.stash_len() == 0, "Stash: {}", hash_table.stash_len());
+    hash_table.assert_neighborhoods_are_consistent();
+
+    // A table created with no capacity gets one bucket, and grows from there.
+    let mut hash_table = HopscotchHashTable::new(0);
+    assert!(hash_table.capacity == 1, "Capacity: {}", hash_table.capacity);
+    assert!(hash_table.get(&1).is_none());
+    for key in 0..100 {
+        hash_table.insert(key, key);
+    }
+    assert!((0..100).all(|key| hash_table.get(&key) == Some(&key)));
+    println!("All tests passed");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    hash_table::hash_table_tests!(HopscotchHashTable);
+}
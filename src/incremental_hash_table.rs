@@ -0,0 +1,490 @@
+use std::hash::Hash;
+use std::time::{Duration, Instant};
+
+use crate::bucketized_cuckoo_hash_table::BucketizedCuckooHashTable;
+use crate::cuckoo_hash_table::CuckooHashTable;
+use crate::dhts_hash_table::DoubleHashingTombstoneHashTable;
+use crate::hash_table::{BuildIdentityHasher, Entry, HashTable, MigrateBuckets};
+use crate::hopscotch_hash_table::HopscotchHashTable;
+use crate::lprh_hash_table::LinearProbingRobinHoodHashTable;
+use crate::lpts_hash_table::LinearProbingTombstoneHashTable;
+use crate::qpts_hash_table::QuadraticProbingTombstoneHashTable;
+use crate::sc_hash_table::{LinkedListChainingHashTable, SeparateChainingHashTable, SmallVecChainingHashTable};
+use crate::swiss_hash_table::SwissHashTable;
+
+// Enough to finish migrating long before the new table fills up with the default load and growth factors.
+const DEFAULT_BUCKETS_PER_STEP: usize = 8;
+
+/// Wraps any table of the crate so that it resizes incrementally. When the table has to grow or compact, the
+/// new table starts out empty and the old one is kept next to it. Every operation that changes the table then
+/// moves the entries of a few old buckets over, and lookups check both tables until the old one is empty. This
+/// spreads the cost of rehashing over many operations instead of pausing a single insertion. The new table is
+/// presized to hold everything it can receive before the migration is done, so it never resizes itself in the
+/// middle of one. Cuckoo tables can still rehash in place when an insertion runs into a cycle, and hopscotch
+/// tables grow when a neighborhood has no room left.
+pub struct IncrementalHashTable<T> {
+    table: T,
+    // Table whose entries are still being moved into `table`, while a resize is in progress.
+    old_table: Option<T>,
+    // Every bucket of `old_table` before this one has been migrated.
+    migration_cursor: usize,
+    buckets_per_step: usize,
+}
+
+impl<T> IncrementalHashTable<T> {
+    pub fn new(table: T) -> Self {
+        IncrementalHashTable { table, old_table: None, migration_cursor: 0, buckets_per_step: DEFAULT_BUCKETS_PER_STEP }
+    }
+
+    /// Number of old buckets migrated by every operation that changes the table. Fewer buckets per step mean
+    /// less work per operation, but a longer migration, and so more room set aside in the new table for the
+    /// entries inserted in the meantime.
+    pub fn set_buckets_per_step(&mut self, buckets_per_step: usize) {
+        assert!(buckets_per_step > 0, "Buckets per step must be positive, got {}", buckets_per_step);
+        self.buckets_per_step = buckets_per_step;
+    }
+
+    /// Whether a resize is in progress, with entries left to migrate out of the old table.
+    pub fn is_migrating(&self) -> bool {
+        self.old_table.is_some()
+    }
+
+    // Migrates up to `buckets` buckets of the old table, and drops it once it is empty.
+    fn migrate<K: Hash + Eq, V>(&mut self, buckets: usize)
+    where
+        T: MigrateBuckets<K, V>,
+    {
+        let Some(old_table) = &mut self.old_table else {
+            return;
+        };
+        for _ in 0..buckets {
+            if old_table.is_empty() {
+                break;
+            }
+            assert!(self.migration_cursor < old_table.bucket_count(), "Entries left behind the migration cursor");
+            // Taking a bucket may pull a later entry into it, so only move on once the bucket stays empty.
+            loop {
+                let mut migrated = false;
+                for (key, value) in old_table.take_bucket(self.migration_cursor) {
+                    self.table.insert(key, value);
+                    migrated = true;
+                }
+                if !migrated {
+                    break;
+                }
+            }
+            self.migration_cursor += 1;
+        }
+        if old_table.is_empty() {
+            self.old_table = None;
+            self.migration_cursor = 0;
+        }
+    }
+
+    fn finish_migration<K: Hash + Eq, V>(&mut self)
+    where
+        T: MigrateBuckets<K, V>,
+    {
+        self.migrate(usize::MAX);
+    }
+
+    // Moves the bucket holding `key` out of the old table, so that the key can be changed or removed in the
+    // new table only.
+    fn promote<K: Hash + Eq, V>(&mut self, key: &K)
+    where
+        T: MigrateBuckets<K, V>,
+    {
+        let Some(old_table) = &mut self.old_table else {
+            return;
+        };
+        if let Some(index) = old_table.bucket_of(key) {
+            for (key, value) in old_table.take_bucket(index) {
+                self.table.insert(key, value);
+            }
+        }
+    }
+
+    // Starts a resize instead of letting the table resize itself on the next insertion.
+    fn start_resize_if_needed<K: Hash + Eq, V>(&mut self)
+    where
+        T: MigrateBuckets<K, V>,
+    {
+        if self.old_table.is_some() && self.table.capacity_to_resize_to().is_some() {
+            // The new table filled up before the migration was done, which presizing rules out unless the
+            // buckets per step went down in the middle of it. Finish it, so that there is never more than one old
+            // table.
+            self.finish_migration();
+        }
+        if let Some(capacity) = self.table.capacity_to_resize_to() {
+            // Every operation until the migration is done adds at most one entry, or tombstone, to the new table.
+            let operations = self.table.bucket_count().div_ceil(self.buckets_per_step);
+            let capacity = capacity.max(self.table.capacity_for(self.table.len() + operations + 1));
+            let new_table = self.table.empty_with_capacity(capacity);
+            self.old_table = Some(std::mem::replace(&mut self.table, new_table));
+            self.migration_cursor = 0;
+        }
+    }
+}
+
+impl<K: Hash + Eq, V, T: MigrateBuckets<K, V>> HashTable<K, V> for IncrementalHashTable<T> {
+    fn len(&self) -> usize {
+        self.table.len() + self.old_table.as_ref().map_or(0, HashTable::len)
+    }
+
+    /// Capacity of the new table while a resize is in progress.
+    fn capacity(&self) -> usize {
+        self.table.capacity()
+    }
+
+    fn entry(&mut self, key: K) -> Entry<'_, K, V> {
+        self.start_resize_if_needed();
+        self.promote(&key);
+        self.migrate(self.buckets_per_step);
+        self.table.entry(key)
+    }
+
+    fn get_key_value(&self, key: &K) -> Option<(&K, &V)> {
+        self.table.get_key_value(key).or_else(|| self.old_table.as_ref()?.get_key_value(key))
+    }
+
+    fn get_mut(&mut self, key: &K) -> Option<&mut V> {
+        self.promote(key);
+        self.migrate(self.buckets_per_step);
+        self.table.get_mut(key)
+    }
+
+    fn get_many_mut<const N: usize>(&mut self, keys: [&K; N]) -> Option<[&mut V; N]> {
+        for key in keys {
+            self.promote(key);
+        }
+        self.migrate(self.buckets_per_step);
+        self.table.get_many_mut(keys)
+    }
+
+    fn remove_entry(&mut self, key: &K) -> Option<(K, V)> {
+        self.promote(key);
+        self.migrate(self.buckets_per_step);
+        self.table.remove_entry(key)
+    }
+
+    fn retain<F: FnMut(&K, &mut V) -> bool>(&mut self, f: F) {
+        self.finish_migration();
+        self.table.retain(f);
+    }
+
+    fn clear(&mut self) {
+        self.old_table = None;
+        self.migration_cursor = 0;
+        self.table.clear();
+    }
+
+    fn iter<'a>(&'a self) -> impl ExactSizeIterator<Item = (&'a K, &'a V)>
+    where
+        K: 'a,
+        V: 'a,
+    {
+        Iter { table: self.table.iter(), old_table: self.old_table.as_ref().map(HashTable::iter) }
+    }
+
+    fn iter_mut<'a>(&'a mut self) -> impl ExactSizeIterator<Item = (&'a K, &'a mut V)>
+    where
+        K: 'a,
+        V: 'a,
+    {
+        Iter { table: self.table.iter_mut(), old_table: self.old_table.as_mut().map(HashTable::iter_mut) }
+    }
+
+    // Draining touches every bucket anyway, so the old table is migrated first.
+    fn drain(&mut self) -> impl ExactSizeIterator<Item = (K, V)> {
+        self.finish_migration();
+        self.table.drain()
+    }
+
+    fn shrink_to(&mut self, min_capacity: usize) {
+        self.finish_migration();
+        self.table.shrink_to(min_capacity);
+    }
+}
+
+/// Iterator over the entries of the new table, followed by the entries still in the old one.
+pub struct Iter<I> {
+    table: I,
+    old_table: Option<I>,
+}
+
+impl<I: ExactSizeIterator> Iterator for Iter<I> {
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.table.next().or_else(|| self.old_table.as_mut()?.next())
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.table.len() + self.old_table.as_ref().map_or(0, ExactSizeIterator::len);
+        (remaining, Some(remaining))
+    }
+}
+
+impl<I: ExactSizeIterator> ExactSizeIterator for Iter<I> {}
+
+impl<T: IntoIterator<IntoIter: ExactSizeIterator>> IntoIterator for IncrementalHashTable<T> {
+    type Item = T::Item;
+    type IntoIter = Iter<T::IntoIter>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        Iter { table: self.table.into_iter(), old_table: self.old_table.map(IntoIterator::into_iter) }
+    }
+}
+
+// Implemented for each table rather than for every `T` with `&T: IntoIterator`, which the compiler can't tell
+// apart from `&IncrementalHashTable<IncrementalHashTable<T>>` when inferring the table type.
+macro_rules! impl_borrowed_into_iterator {
+    ($($table:ident<K, V, $($param:ident),*>),* $(,)?) => {$(
+        impl<'a, K: Hash + Eq, V, $($param),*> IntoIterator for &'a IncrementalHashTable<$table<K, V, $($param),*>>
+        where
+            &'a $table<K, V, $($param),*>: IntoIterator<IntoIter: ExactSizeIterator>,
+        {
+            type Item = <&'a $table<K, V, $($param),*> as IntoIterator>::Item;
+            type IntoIter = Iter<<&'a $table<K, V, $($param),*> as IntoIterator>::IntoIter>;
+
+            fn into_iter(self) -> Self::IntoIter {
+                let old_table = self.old_table.as_ref().map(IntoIterator::into_iter);
+                Iter { table: (&self.table).into_iter(), old_table }
+            }
+        }
+
+        impl<'a, K: Hash + Eq, V, $($param),*> IntoIterator for &'a mut IncrementalHashTable<$table<K, V, $($param),*>>
+        where
+            &'a mut $table<K, V, $($param),*>: IntoIterator<IntoIter: ExactSizeIterator>,
+        {
+            type Item = <&'a mut $table<K, V, $($param),*> as IntoIterator>::Item;
+            type IntoIter = Iter<<&'a mut $table<K, V, $($param),*> as IntoIterator>::IntoIter>;
+
+            fn into_iter(self) -> Self::IntoIter {
+                let old_table = self.old_table.as_mut().map(IntoIterator::into_iter);
+                Iter { table: (&mut self.table).into_iter(), old_table }
+            }
+        }
+    )*};
+}
+
+impl_borrowed_into_iterator!(
+    LinearProbingTombstoneHashTable<K, V, S>,
+    QuadraticProbingTombstoneHashTable<K, V, S>,
+    DoubleHashingTombstoneHashTable<K, V, S>,
+    LinearProbingRobinHoodHashTable<K, V, S>,
+    CuckooHashTable<K, V, S>,
+    BucketizedCuckooHashTable<K, V, S>,
+    HopscotchHashTable<K, V, S>,
+    SeparateChainingHashTable<K, V, C, S>,
+    SwissHashTable<K, V, S>,
+);
+
+// Longest single insertion out of `count` consecutive ones.
+fn longest_insert<T: HashTable<usize, usize>>(hash_table: &mut T, count: usize) -> Duration {
+    let mut longest = Duration::ZERO;
+    for key in 0..count {
+        let start = Instant::now();
+        hash_table.insert(key, key);
+        longest = longest.max(start.elapsed());
+    }
+    longest
+}
+
+// Inserts `count` keys and returns the most entries a single insertion moved out of the old table. Panics if the
+// table resized while a migration was in progress: either the new table resized itself, or the migration was
+// finished in one go.
+fn most_entries_migrated_by_one_insertion<T: MigrateBuckets<usize, usize>>(
+    hash_table: &mut IncrementalHashTable<T>,
+    count: usize,
+) -> usize {
+    let mut most = 0;
+    for key in 0..count {
+        let was_migrating = hash_table.is_migrating();
+        let capacity = hash_table.capacity();
+        let len = hash_table.table.len();
+        hash_table.insert(key, key);
+        let migrated = if hash_table.capacity() != capacity {
+            let new_capacity = hash_table.capacity();
+            assert!(!was_migrating, "Resized to {} in the middle of a migration, at key {}", new_capacity, key);
+            // A resize started, and the new table began empty.
+            hash_table.table.len() - 1
+        } else {
+            hash_table.table.len() - len - 1
+        };
+        most = most.max(migrated);
+    }
+    most
+}
+
+// Checks that no single insertion into tables built by `new_table` migrates more than a handful of entries, where
+// resizing in one go moves all of them, thousands by the last resize. Clusters and chains make the exact number vary.
+fn check_bounded_migration<T: MigrateBuckets<usize, usize>>(new_table: impl Fn() -> T) {
+    for buckets_per_step in [1, DEFAULT_BUCKETS_PER_STEP] {
+        let mut hash_table = IncrementalHashTable::new(new_table());
+        hash_table.set_buckets_per_step(buckets_per_step);
+        let most = most_entries_migrated_by_one_insertion(&mut hash_table, 20_000);
+        assert!(most <= 100, "Migrated {} entries in one insertion", most);
+    }
+}
+
+pub fn run() {
+    let capacity: usize = 10;
+    // The identity hasher keeps keys on `key % capacity` so the migration below is predictable.
+    let table = LinearProbingTombstoneHashTable::with_hasher(capacity, BuildIdentityHasher::default());
+    let mut hash_table = IncrementalHashTable::new(table);
+    hash_table.set_buckets_per_step(1);
+
+    for key in 0..7 {
+        hash_table.insert(key, key);
+    }
+    assert!(!hash_table.is_migrating());
+    assert!(hash_table.capacity() == 10);
+
+    // The eighth key takes the table above its max load factor of 0.75. Doubling the capacity to 20 isn't enough:
+    // migrating one bucket at a time takes ten operations, which may insert ten more keys on top of the seven.
+    hash_table.insert(7, 7);
+    println!("Inserted 7, which should start a resize to 24 and migrate bucket 0:");
+    let old_len = hash_table.old_table.as_ref().unwrap().len();
+    println!("New table: {} entries, old table: {} entries", hash_table.table.len(), old_len);
+    assert!(hash_table.is_migrating());
+    assert!(hash_table.capacity() == 24);
+    assert!(hash_table.table.len() == 2 && old_len == 6);
+    assert!(hash_table.len() == 8);
+    for key in 0..8 {
+        assert!(hash_table.get(&key) == Some(&key), "Wrong value for key {}", key);
+    }
+
+    assert!(hash_table.remove(&5) == Some(5));
+    println!("Deleted 5, which should move it out of the old table before removing it, and migrate bucket 1:");
+    let old_len = hash_table.old_table.as_ref().unwrap().len();
+    println!("New table: {} entries, old table: {} entries", hash_table.table.len(), old_len);
+    assert!(hash_table.table.len() == 3 && old_len == 4);
+    assert!(hash_table.get(&5).is_none());
+
+    // Buckets 2, 3, 4 and 6 still hold entries. Bucket 5 is a tombstone, which takes a step of its own.
+    for key in 8..13 {
+        hash_table.insert(key, key);
+    }
+    println!("Inserted 8 to 12, which should finish the migration:");
+    assert!(!hash_table.is_migrating());
+    assert!(hash_table.table.len() == 12);
+    for key in (0..13).filter(|&key| key != 5) {
+        assert!(hash_table.get(&key) == Some(&key), "Wrong value for key {}", key);
+    }
+
+    // Growing a big table pauses a single insertion for the whole rehash, unless it is spread out.
+    let count = 1 << 17;
+    let longest = longest_insert(&mut LinearProbingTombstoneHashTable::new(capacity), count);
+    println!("Longest of {} insertions when resizing in one go: {:?}", count, longest);
+    let longest = longest_insert(&mut IncrementalHashTable::new(LinearProbingTombstoneHashTable::new(capacity)), count);
+    println!("Longest of {} insertions when resizing incrementally: {:?}", count, longest);
+
+    check_bounded_migration(|| LinearProbingTombstoneHashTable::new(capacity));
+    check_bounded_migration(|| QuadraticProbingTombstoneHashTable::new(capacity));
+    check_bounded_migration(|| DoubleHashingTombstoneHashTable::new(capacity));
+    check_bounded_migration(|| LinearProbingRobinHoodHashTable::new(capacity));
+    check_bounded_migration(|| CuckooHashTable::new(capacity));
+    check_bounded_migration(|| BucketizedCuckooHashTable::new(capacity));
+    // Hopscotch tables also grow when a key's neighborhood has no room left, which presizing can't rule out near
+    // the default max load factor of 0.9.
+    check_bounded_migration(|| {
+        let mut hash_table = HopscotchHashTable::new(capacity);
+        hash_table.set_max_load_factor(0.8);
+        hash_table
+    });
+    check_bounded_migration(|| LinkedListChainingHashTable::new(capacity));
+    check_bounded_migration(|| SmallVecChainingHashTable::new(capacity));
+    check_bounded_migration(|| SwissHashTable::new(capacity));
+    println!("All tests passed");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hash_table::{
+        check_automatic_shrinking, check_entry, check_hash_table, check_hash_table_with_string_keys, check_iterators,
+        check_load_factor, check_lookups, check_removal, check_shrinking,
+    };
+
+    // Runs the shared checks against the table built by `$new`, resized incrementally.
+    macro_rules! check_incremental_resizing {
+        ($new:expr) => {{
+            check_hash_table(&mut IncrementalHashTable::new($new));
+            check_hash_table_with_string_keys(&mut IncrementalHashTable::new($new));
+            let mut hash_table = $new;
+            hash_table.set_max_load_factor(0.6);
+            hash_table.set_growth_factor(1.5);
+            check_load_factor(&mut IncrementalHashTable::new(hash_table), 0.6, 1.5);
+            check_shrinking(&mut IncrementalHashTable::new($new));
+            let mut hash_table = $new;
+            hash_table.set_min_load_factor(Some(0.2));
+            check_automatic_shrinking(&mut IncrementalHashTable::new(hash_table));
+            check_iterators(IncrementalHashTable::new($new));
+            check_entry(&mut IncrementalHashTable::new($new));
+            check_lookups(&mut IncrementalHashTable::new($new));
+            check_removal(&mut IncrementalHashTable::new($new));
+
+            // Migrating a single bucket per operation keeps a resize in progress for most of the checks.
+            let mut hash_table = IncrementalHashTable::new($new);
+            hash_table.set_buckets_per_step(1);
+            check_hash_table(&mut hash_table);
+            let mut hash_table = IncrementalHashTable::new($new);
+            hash_table.set_buckets_per_step(1);
+            check_iterators(hash_table);
+            let mut hash_table = IncrementalHashTable::new($new);
+            hash_table.set_buckets_per_step(1);
+            check_removal(&mut hash_table);
+        }};
+    }
+
+    #[test]
+    fn resizes_lpts_incrementally() {
+        check_incremental_resizing!(LinearProbingTombstoneHashTable::new(10));
+    }
+
+    #[test]
+    fn resizes_qpts_incrementally() {
+        check_incremental_resizing!(QuadraticProbingTombstoneHashTable::new(10));
+    }
+
+    #[test]
+    fn resizes_dhts_incrementally() {
+        check_incremental_resizing!(DoubleHashingTombstoneHashTable::new(10));
+    }
+
+    #[test]
+    fn resizes_lprh_incrementally() {
+        check_incremental_resizing!(LinearProbingRobinHoodHashTable::new(10));
+    }
+
+    #[test]
+    fn resizes_cuckoo_incrementally() {
+        check_incremental_resizing!(CuckooHashTable::new(10));
+    }
+
+    #[test]
+    fn resizes_bucketized_cuckoo_incrementally() {
+        check_incremental_resizing!(BucketizedCuckooHashTable::new(10));
+    }
+
+    #[test]
+    fn resizes_hopscotch_incrementally() {
+        check_incremental_resizing!(HopscotchHashTable::new(10));
+    }
+
+    #[test]
+    fn resizes_linked_list_chaining_incrementally() {
+        check_incremental_resizing!(LinkedListChainingHashTable::new(10));
+    }
+
+    #[test]
+    fn resizes_small_vec_chaining_incrementally() {
+        check_incremental_resizing!(SmallVecChainingHashTable::new(10));
+    }
+
+    #[test]
+    fn resizes_swiss_incrementally() {
+        check_incremental_resizing!(SwissHashTable::new(10));
+    }
+}
@@ -1,7 +1,9 @@
 use crate::print_time;
 
-fn kadane(arr: &[f64]) -> (f64, usize, usize) {
-    if arr.len() == 0 {
+/// Largest sum of a contiguous subarray of `arr`, with the indices of its first and last items.
+/// Panics if `arr` is empty.
+pub fn kadane(arr: &[f64]) -> (f64, usize, usize) {
+    if arr.is_empty() {
         panic!("Empty array");
     }
     let mut max_to_here: f64 = arr[0];
@@ -9,12 +11,12 @@ fn kadane(arr: &[f64]) -> (f64, usize, usize) {
     let mut start: usize = 0;
     let mut end: usize = 0;
     let mut potential_start: usize = 0;
-    for x in 0..arr.len() {
-        if arr[x] > max_to_here + arr[x] {
+    for (x, &item) in arr.iter().enumerate() {
+        if item > max_to_here + item {
             potential_start = x;
-            max_to_here = arr[x];
+            max_to_here = item;
         } else {
-            max_to_here = arr[x] + max_to_here;
+            max_to_here += item;
         }
         if max_to_here > max_so_far {
             end = x;
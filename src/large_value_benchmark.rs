@@ -0,0 +1,128 @@
+use std::collections::HashMap;
+use std::collections::hash_map::RandomState;
+use std::hash::BuildHasher;
+use std::time::{Duration, Instant};
+
+use alg::cuckoo_hash_table::CuckooHashTable;
+use alg::hash_table::HashTable;
+use alg::lprh_hash_table::LinearProbingRobinHoodHashTable;
+
+const ENTRIES: usize = 2000;
+const VALUE_SIZES: [usize; 4] = [16, 1 << 10, 8 << 10, 32 << 10];
+
+// Deliberately not `Clone`: the tables have to move values around during displacement, so this only
+// compiles if no insertion path clones a value.
+struct LargeValue {
+    bytes: Vec<u8>,
+}
+
+// Robin Hood insertion as this crate first wrote it, cloning the value being inserted and the occupant of every
+// bucket it probes, kept as the baseline that moving entries is measured against. It uses the same hashing, max
+// load factor and growth as `LinearProbingRobinHoodHashTable`.
+struct CloningRobinHoodHashTable<V> {
+    table: Vec<Option<(usize, usize, V)>>,
+    size: usize,
+    hash_builder: RandomState,
+}
+
+impl<V: Clone> CloningRobinHoodHashTable<V> {
+    fn new(capacity: usize) -> Self {
+        CloningRobinHoodHashTable { table: vec![None; capacity], size: 0, hash_builder: RandomState::new() }
+    }
+
+    fn get_preferred_index(&self, key: usize) -> usize {
+        self.hash_builder.hash_one(key) as usize % self.table.len()
+    }
+
+    fn insert(&mut self, key: usize, value: V) {
+        if (self.size + 1) as f64 > self.table.len() as f64 * 0.875 {
+            let new_table = vec![None; self.table.len() * 2];
+            let old_table = std::mem::replace(&mut self.table, new_table);
+            self.size = 0;
+            for (key, _, value) in old_table.into_iter().flatten() {
+                self.insert(key, value);
+            }
+        }
+        let mut preferred_index = self.get_preferred_index(key);
+        let mut i = 0;
+        let mut target_key = key;
+        let mut target_value = value.clone();
+        loop {
+            let probe_index = (preferred_index + i) % self.table.len();
+            if let Some((existing_key, existing_offset, existing_value)) = &self.table[probe_index].clone() {
+                if *existing_key == target_key {
+                    self.table[probe_index] = Some((target_key, i, target_value));
+                    return;
+                } else if *existing_offset < i {
+                    self.table[probe_index] = Some((target_key, i, target_value));
+                    preferred_index = (probe_index + self.table.len() - existing_offset) % self.table.len();
+                    target_key = *existing_key;
+                    target_value = existing_value.clone();
+                    i = *existing_offset;
+                }
+            } else {
+                self.table[probe_index] = Some((target_key, i, target_value));
+                self.size += 1;
+                return;
+            }
+            i += 1;
+        }
+    }
+}
+
+fn large_values(value_size: usize) -> Vec<LargeValue> {
+    (0..ENTRIES).map(|i| LargeValue { bytes: vec![i as u8; value_size] }).collect()
+}
+
+fn time_inserts<T: HashTable<usize, LargeValue>>(mut hash_table: T, values: Vec<LargeValue>) -> Duration {
+    let start = Instant::now();
+    for (key, value) in values.into_iter().enumerate() {
+        hash_table.insert(key, value);
+    }
+    let elapsed = start.elapsed();
+    assert!(hash_table.len() == ENTRIES, "Size: {}", hash_table.len());
+    assert!(hash_table.get(&7).unwrap().bytes[0] == 7);
+    elapsed
+}
+
+fn time_std_inserts(values: Vec<LargeValue>) -> Duration {
+    let mut hash_map = HashMap::with_capacity(10);
+    let start = Instant::now();
+    for (key, value) in values.into_iter().enumerate() {
+        hash_map.insert(key, value);
+    }
+    let elapsed = start.elapsed();
+    assert!(hash_map.len() == ENTRIES, "Size: {}", hash_map.len());
+    elapsed
+}
+
+fn time_cloning_inserts(values: Vec<LargeValue>) -> Duration {
+    let values: Vec<_> = values.into_iter().map(|value| value.bytes).collect();
+    let mut hash_table = CloningRobinHoodHashTable::new(10);
+    let start = Instant::now();
+    for (key, value) in values.into_iter().enumerate() {
+        hash_table.insert(key, value);
+    }
+    let elapsed = start.elapsed();
+    assert!(hash_table.size == ENTRIES, "Size: {}", hash_table.size);
+    elapsed
+}
+
+fn print_per_insert(name: &str, value_size: usize, elapsed: Duration) {
+    println!("{:>18} {:>6} byte values: {:?} per insert", name, value_size, elapsed / ENTRIES as u32);
+}
+
+// Times inserting values of growing sizes. Values are allocated before the clock starts, so with
+// displacement done by moving entries the time per insert should barely change with the value size, while the
+// cloning baseline copies every value it displaces and slows down as they grow.
+// Run with `--release` for meaningful numbers.
+pub fn run() {
+    for value_size in VALUE_SIZES {
+        let robin_hood = LinearProbingRobinHoodHashTable::new(10);
+        print_per_insert("Robin Hood", value_size, time_inserts(robin_hood, large_values(value_size)));
+        print_per_insert("Cloning Robin Hood", value_size, time_cloning_inserts(large_values(value_size)));
+        let cuckoo = CuckooHashTable::new(10);
+        print_per_insert("Cuckoo", value_size, time_inserts(cuckoo, large_values(value_size)));
+        print_per_insert("std HashMap", value_size, time_std_inserts(large_values(value_size)));
+    }
+}
@@ -0,0 +1,20 @@
+//! Hash tables, Bloom filters and a few small algorithms, each with a `run` function that demonstrates and checks
+//! it. The `alg` binary picks one of those `run` functions by name.
+
+mod print_time;
+pub mod menu;
+pub mod kadane;
+pub mod common_chars;
+pub mod lpts_hash_table;
+pub mod lprh_hash_table;
+pub mod qpts_hash_table;
+pub mod dhts_hash_table;
+pub mod cuckoo_hash_table;
+pub mod bucketized_cuckoo_hash_table;
+pub mod hopscotch_hash_table;
+pub mod sc_hash_table;
+pub mod swiss_hash_table;
+pub mod bloom_filter;
+pub mod counting_bloom_filter;
+pub mod hash_table;
+pub mod incremental_hash_table;
@@ -1,158 +1,184 @@
-struct LinearProbingRobinHoodHashTable {
-    table: Vec<Option<(usize, usize, Vec<char>)>>,
+use std::collections::hash_map::RandomState;
+use std::fmt::Debug;
+use std::hash::{BuildHasher, Hash};
+
+use crate::hash_table::{
+    self, BuildIdentityHasher, Entry, HashTable, HashTableStats, InsertAtBucket, MigrateBuckets, OccupiedEntry, Stats,
+    VacantEntry,
+};
+
+// Robin Hood hashing keeps probe lengths even, so it copes with much fuller tables than plain linear probing.
+const DEFAULT_MAX_LOAD_FACTOR: f64 = 0.875;
+
+pub struct LinearProbingRobinHoodHashTable<K, V, S = RandomState> {
+    // Each occupied bucket stores the key, its offset from the preferred index and the value.
+    table: Vec<Option<(K, usize, V)>>,
     capacity: usize,
     size: usize,
+    hash_builder: S,
+    max_load_factor: f64,
+    growth_factor: f64,
+    // When set, removals shrink the table once the load factor drops below this value.
+    min_load_factor: Option<f64>,
+}
+
+impl<K: Hash + Eq, V> LinearProbingRobinHoodHashTable<K, V> {
+    pub fn new(capacity: usize) -> Self {
+        Self::with_hasher(capacity, RandomState::new())
+    }
 }
 
-impl LinearProbingRobinHoodHashTable {
-    fn new(capacity: usize) -> Self {
+impl<K: Hash + Eq, V, S: BuildHasher> LinearProbingRobinHoodHashTable<K, V, S> {
+    pub fn with_hasher(capacity: usize, hash_builder: S) -> Self {
         LinearProbingRobinHoodHashTable {
-            table: vec![None; capacity],
+            table: hash_table::empty_buckets(capacity),
             capacity,
             size: 0,
+            hash_builder,
+            max_load_factor: DEFAULT_MAX_LOAD_FACTOR,
+            growth_factor: hash_table::DEFAULT_GROWTH_FACTOR,
+            min_load_factor: None,
         }
     }
 
-    fn get_preferred_index(&self, key: usize) -> usize {
-        // For testing simplicity, we just mod the key by the capacity.
-        // We would ideally use a proper hash function here.
-        key % self.capacity
+    /// The table grows before an insertion would take it above this fraction of its capacity.
+    pub fn set_max_load_factor(&mut self, max_load_factor: f64) {
+        hash_table::assert_valid_max_load_factor(max_load_factor);
+        hash_table::assert_valid_min_load_factor(self.min_load_factor, max_load_factor);
+        self.max_load_factor = max_load_factor;
+    }
+
+    /// Makes removals shrink the table once its load factor drops below `min_load_factor`.
+    /// `None`, the default, never shrinks automatically.
+    pub fn set_min_load_factor(&mut self, min_load_factor: Option<f64>) {
+        hash_table::assert_valid_min_load_factor(min_load_factor, self.max_load_factor);
+        self.min_load_factor = min_load_factor;
+    }
+
+    /// Factor the capacity is multiplied by whenever the table grows.
+    pub fn set_growth_factor(&mut self, growth_factor: f64) {
+        hash_table::assert_valid_growth_factor(growth_factor);
+        self.growth_factor = growth_factor;
+    }
+
+    fn get_preferred_index(&self, key: &K) -> usize {
+        (self.hash_builder.hash_one(key) % self.capacity as u64) as usize
     }
 
     fn resize_to(&mut self, new_capacity: usize) -> bool {
-        if new_capacity < self.capacity {
+        if !hash_table::fits_in_capacity(self.size, new_capacity, self.max_load_factor) {
             return false;
         }
         self.capacity = new_capacity;
-        let old_table = std::mem::take(&mut self.table);
-        self.table = vec![None; self.capacity];
+        let old_table = std::mem::replace(&mut self.table, hash_table::empty_buckets(self.capacity));
         self.size = 0;
-        for entry in old_table {
-            if let Some((key, _, value)) = entry {
-                self.insert(key, value);
-            }
+        for (key, _, value) in old_table.into_iter().flatten() {
+            self.insert(key, value);
         }
-        return true;
+        true
     }
 
-    fn resize(&mut self) {
-        assert!(self.resize_to(self.capacity * 2));
+    // Shrinks the table if removals took it below the minimum load factor.
+    fn shrink_if_underloaded(&mut self) {
+        if let Some(min_load_factor) = self.min_load_factor
+            && (self.size as f64) < self.capacity as f64 * min_load_factor
+        {
+            self.shrink_to(hash_table::shrunk_capacity(self.size, min_load_factor, self.max_load_factor));
+        }
     }
 
-    fn insert(&mut self, key: usize, value: Vec<char>) {
-        // Resize the table because it's full.
-        if self.size >= self.capacity {
-            self.resize();
-        }
+    fn resize(&mut self) {
+        assert!(self.resize_to(hash_table::grown_capacity(self.capacity, self.growth_factor)));
+    }
 
-        let mut preferred_index = self.get_preferred_index(key);
-        let mut i= 0;
-        let mut target_key = key;
-        let mut target_value = value.clone();
+    // Probes for `key` once. Returns `Ok` with the bucket holding the key, or `Err` with the bucket a new
+    // entry for the key goes into: either an empty bucket, or the first bucket whose occupant is closer to
+    // its preferred bucket than the key would be, since the key takes that bucket from it.
+    fn find_bucket_for_insert(&self, key: &K) -> Result<usize, usize> {
+        let preferred_index = self.get_preferred_index(key);
 
-        // Loop until a free location is found.
         // No need to check if the table is full during probing because the table is scaled as needed
-        // at the beginning of this method.
+        // before probing, so there is always an empty bucket.
+        let mut i = 0;
         loop {
             // Loop around to the front of the vector as needed.
             let probe_index = (preferred_index + i) % self.capacity;
 
-            if self.table[probe_index].is_none() {
-                // Empty bucket found, insert at found empty bucket
-                self.table[probe_index] = Some((target_key, i, target_value));
-                self.size += 1;
-                return;
-            } else if let Some((existing_key, existing_offset, existing_value)) = &self.table[probe_index].clone() {
-                if *existing_key == target_key {
-                    // Key already exists and we didn't find a tombstone during probing, replace the value
-                    self.table[probe_index] = Some((target_key, i, target_value));
-                    return;
-                } else if *existing_offset < i {
-                    // Evict existing bucket occupant because its offset is lower than current item's
-                    // Continue evicting subsequent items until an empty bucket is found.
-                    self.table[probe_index] = Some((target_key, i, target_value));
-                    preferred_index = probe_index - existing_offset;
-                    target_key = *existing_key;
-                    target_value = existing_value.clone();
-                    i = *existing_offset;
-                    continue;
+            match &self.table[probe_index] {
+                None => return Err(probe_index),
+                Some((existing_key, existing_offset, _)) => {
+                    if existing_key == key {
+                        return Ok(probe_index);
+                    } else if *existing_offset < i {
+                        return Err(probe_index);
+                    }
                 }
             }
             i += 1;
         }
     }
 
-    fn find_index_for_key_if_exists(&mut self, key: usize) -> Option<usize> {
-        let preferred_index = self.get_preferred_index(key);
+    fn find_index_for_key_if_exists(&self, key: &K) -> Option<usize> {
+        self.probe(self.get_preferred_index(key), |existing_key| existing_key == key).0
+    }
 
+    // Probes from `preferred_index` until `is_match` accepts a key or probing shows that no key with this
+    // preferred bucket is left. Returns the bucket of the accepted key, if any, and the number of buckets
+    // inspected.
+    fn probe(&self, preferred_index: usize, is_match: impl Fn(&K) -> bool) -> (Option<usize>, usize) {
         for i in 0..self.capacity {
             let probe_index: usize = (preferred_index + i) % self.capacity;
 
             if let Some((existing_key, offset, _)) = &self.table[probe_index] {
                 // We've hit an occupied bucket
-                if *existing_key == key {
+                if is_match(existing_key) {
                     // Key matches; we have a hit.
-                    return Some(probe_index);
+                    return (Some(probe_index), i + 1);
                 } else if *offset < i {
                     // Since offset is less than the current occupant's offset, the key is not in the table.
                     // If it were in the table, we would have found it already during probing.
-                    return None;
+                    return (None, i + 1);
                 }
                 // Else continue probing
             } else {
                 // We've hit an empty bucket during probing.
-                return None;
+                return (None, i + 1);
             }
         }
         // Table is full, key not found after wrap-around probing
-        return None;
-
+        (None, self.capacity)
     }
 
-    fn get(&mut self, key: usize) -> Option<&Vec<char>> {
-        let found_index = self.find_index_for_key_if_exists(key);
-        if found_index.is_none() {
-            return None;
-        }
-        if let Some((_, _, value)) = &self.table[found_index.unwrap()] {
-            return Some(value);
-        } else {
-            return None;
-        }
-    }
+    // Takes the entry out of the bucket at `index`, then shifts the entries after it back by one bucket until
+    // an empty bucket or an entry that is already in its preferred bucket. Returns the removed entry and the
+    // bucket left empty at the end of the shift.
+    fn remove_at(&mut self, index: usize) -> ((K, V), usize) {
+        let Some((key, _, value)) = self.table[index].take() else {
+            panic!("No entry to remove at index {}", index);
+        };
+        self.size -= 1;
 
-    fn delete(&mut self, key: usize) {
-        let found_index = self.find_index_for_key_if_exists(key);
-        if found_index.is_none() {
-            return;
-        }
-        let mut target_index = found_index.unwrap();
+        let mut target_index = index;
         loop {
             let next_index = (target_index + 1) % self.capacity;
-            if let Some((next_key, next_offset, next_value)) = &self.table[next_index].clone() {
-                if *next_offset > 0 {
-                    self.table[target_index] = Some((*next_key, *next_offset - 1, next_value.clone()));
-                    target_index = next_index;
-                } else {
-                    self.table[target_index] = None;
-                    self.size -= 1;
-                    return;
-                }
+            if let Some((next_key, next_offset, next_value)) = self.table[next_index].take_if(|entry| entry.1 > 0) {
+                self.table[target_index] = Some((next_key, next_offset - 1, next_value));
+                target_index = next_index;
             } else {
-                self.table[target_index] = None;
-                self.size -= 1;
-                return
+                return ((key, value), target_index);
             }
         }
     }
+}
 
-    fn print(&self) {
+impl<K: Debug, V: Debug, S> LinearProbingRobinHoodHashTable<K, V, S> {
+    pub fn print(&self) {
         println!("Capacity: {}", self.capacity);
         println!("Size: {}", self.size);
         println!("Entries:");
         for entry in &self.table {
             if let Some((key, offset, value)) = entry {
-                println!("\t{} (offset: {}): {}", key, offset, value.iter().collect::<String>());
+                println!("\t{:?} (offset: {}): {:?}", key, offset, value);
             } else {
                 println!("\t<None>");
             }
@@ -160,9 +186,236 @@ impl LinearProbingRobinHoodHashTable {
     }
 }
 
+impl<K: Hash + Eq, V, S: BuildHasher> HashTable<K, V> for LinearProbingRobinHoodHashTable<K, V, S> {
+    fn len(&self) -> usize {
+        self.size
+    }
+
+    fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    fn entry(&mut self, key: K) -> Entry<'_, K, V> {
+        // Resize the table because it's getting too full.
+        if hash_table::exceeds_max_load_factor(self.size, self.capacity, self.max_load_factor) {
+            self.resize();
+        }
+
+        match self.find_bucket_for_insert(&key) {
+            Ok(index) => Entry::Occupied(OccupiedEntry::from_bucket(&mut self.table[index])),
+            Err(index) => Entry::Vacant(VacantEntry::new(key, index, self)),
+        }
+    }
+
+    fn get_key_value(&self, key: &K) -> Option<(&K, &V)> {
+        let found_index = self.find_index_for_key_if_exists(key)?;
+        if let Some((key, _, value)) = &self.table[found_index] {
+            Some((key, value))
+        } else {
+            None
+        }
+    }
+
+    fn get_mut(&mut self, key: &K) -> Option<&mut V> {
+        let found_index = self.find_index_for_key_if_exists(key)?;
+        if let Some((_, _, value)) = &mut self.table[found_index] {
+            Some(value)
+        } else {
+            None
+        }
+    }
+
+    fn get_many_mut<const N: usize>(&mut self, keys: [&K; N]) -> Option<[&mut V; N]> {
+        let mut indices = [0; N];
+        for (index, key) in indices.iter_mut().zip(keys) {
+            *index = self.find_index_for_key_if_exists(key)?;
+        }
+        hash_table::values_at_disjoint_buckets(&mut self.table, indices)
+    }
+
+    fn remove_entry(&mut self, key: &K) -> Option<(K, V)> {
+        let found_index = self.find_index_for_key_if_exists(key)?;
+        let (entry, _) = self.remove_at(found_index);
+        self.shrink_if_underloaded();
+        Some(entry)
+    }
+
+    fn retain<F: FnMut(&K, &mut V) -> bool>(&mut self, mut f: F) {
+        // A backward shift that wraps around the end of the table moves entries that were already visited
+        // into buckets that haven't been visited yet. Track which entries were kept and shift the flags along
+        // with the entries, so that `f` sees every entry exactly once.
+        let mut kept = vec![false; self.capacity];
+        for index in 0..self.capacity {
+            while !kept[index]
+                && let Some((key, _, value)) = &mut self.table[index]
+            {
+                if f(key, value) {
+                    kept[index] = true;
+                } else {
+                    let (_, emptied_index) = self.remove_at(index);
+                    let mut target_index = index;
+                    while target_index != emptied_index {
+                        let next_index = (target_index + 1) % self.capacity;
+                        kept[target_index] = kept[next_index];
+                        target_index = next_index;
+                    }
+                    kept[emptied_index] = false;
+                }
+            }
+        }
+        self.shrink_if_underloaded();
+    }
+
+    fn clear(&mut self) {
+        self.table = hash_table::empty_buckets(self.capacity);
+        self.size = 0;
+    }
+
+    fn iter<'a>(&'a self) -> impl ExactSizeIterator<Item = (&'a K, &'a V)>
+    where
+        K: 'a,
+        V: 'a,
+    {
+        hash_table::Iter::new(&self.table, self.size)
+    }
+
+    fn iter_mut<'a>(&'a mut self) -> impl ExactSizeIterator<Item = (&'a K, &'a mut V)>
+    where
+        K: 'a,
+        V: 'a,
+    {
+        hash_table::IterMut::new(&mut self.table, self.size)
+    }
+
+    fn drain(&mut self) -> impl ExactSizeIterator<Item = (K, V)> {
+        let len = self.size;
+        self.size = 0;
+        hash_table::Drain::new(&mut self.table, len)
+    }
+
+    fn shrink_to(&mut self, min_capacity: usize) {
+        let new_capacity = min_capacity.max(hash_table::min_capacity_for(self.size, self.max_load_factor));
+        if new_capacity < self.capacity {
+            assert!(self.resize_to(new_capacity));
+        }
+    }
+}
+
+impl<K: Hash + Eq, V, S: BuildHasher> InsertAtBucket<K, V> for LinearProbingRobinHoodHashTable<K, V, S> {
+    fn insert_at_bucket(&mut self, index: usize, key: K, value: V) -> &mut V {
+        let offset = (index + self.capacity - self.get_preferred_index(&key)) % self.capacity;
+        let mut evicted = self.table[index].replace((key, offset, value));
+        self.size += 1;
+
+        // Evict existing bucket occupants whose offset is lower than the current item's. Continue evicting
+        // subsequent items until an empty bucket is found.
+        let mut probe_index = index;
+        while let Some(mut entry) = evicted.take() {
+            probe_index = (probe_index + 1) % self.capacity;
+            entry.1 += 1;
+            match &mut self.table[probe_index] {
+                bucket @ None => *bucket = Some(entry),
+                Some(existing) => {
+                    if existing.1 < entry.1 {
+                        std::mem::swap(existing, &mut entry);
+                    }
+                    evicted = Some(entry);
+                }
+            }
+        }
+
+        match &mut self.table[index] {
+            Some((_, _, value)) => value,
+            None => unreachable!(),
+        }
+    }
+}
+
+impl<K: Hash + Eq, V, S: BuildHasher + Clone> MigrateBuckets<K, V> for LinearProbingRobinHoodHashTable<K, V, S> {
+    fn capacity_to_resize_to(&self) -> Option<usize> {
+        hash_table::exceeds_max_load_factor(self.size, self.capacity, self.max_load_factor)
+            .then(|| hash_table::grown_capacity(self.capacity, self.growth_factor))
+    }
+
+    fn capacity_for(&self, len: usize) -> usize {
+        hash_table::min_capacity_for(len, self.max_load_factor)
+    }
+
+    fn empty_with_capacity(&self, capacity: usize) -> Self {
+        let mut hash_table = Self::with_hasher(capacity, self.hash_builder.clone());
+        hash_table.max_load_factor = self.max_load_factor;
+        hash_table.growth_factor = self.growth_factor;
+        hash_table.min_load_factor = self.min_load_factor;
+        hash_table
+    }
+
+    fn bucket_count(&self) -> usize {
+        self.table.len()
+    }
+
+    fn bucket_of(&self, key: &K) -> Option<usize> {
+        self.find_index_for_key_if_exists(key)
+    }
+
+    fn take_bucket(&mut self, index: usize) -> impl Iterator<Item = (K, V)> {
+        // The backward shift only pulls the entries after `index` back by one bucket, and stops at the first
+        // empty bucket.
+        let entry = self.table[index].is_some().then(|| self.remove_at(index).0);
+        entry.into_iter()
+    }
+}
+
+impl<K: Hash + Eq, V, S: BuildHasher> HashTableStats for LinearProbingRobinHoodHashTable<K, V, S> {
+    fn stats(&self) -> Stats {
+        let hits = self.keys().map(|key| {
+            self.probe(self.get_preferred_index(key), |existing_key| existing_key == key).1
+        });
+        // A miss stops at the first entry closer to its preferred bucket than the missing key would be.
+        let misses = (0..self.capacity).map(|index| self.probe(index, |_| false).1);
+        let mut stats = Stats::new(self.size, self.capacity, hits.collect(), misses.collect());
+        stats.longest_cluster = hash_table::longest_cluster(&self.table);
+        let mut offsets = Vec::new();
+        for (_, offset, _) in self.table.iter().flatten() {
+            hash_table::add_to_histogram(&mut offsets, *offset);
+        }
+        stats.robin_hood_offsets = Some(offsets);
+        stats
+    }
+}
+
+impl<K, V, S> IntoIterator for LinearProbingRobinHoodHashTable<K, V, S> {
+    type Item = (K, V);
+    type IntoIter = hash_table::IntoIter<Option<(K, usize, V)>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        hash_table::IntoIter::new(self.table, self.size)
+    }
+}
+
+impl<'a, K, V, S> IntoIterator for &'a LinearProbingRobinHoodHashTable<K, V, S> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = hash_table::Iter<'a, Option<(K, usize, V)>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        hash_table::Iter::new(&self.table, self.size)
+    }
+}
+
+impl<'a, K, V, S> IntoIterator for &'a mut LinearProbingRobinHoodHashTable<K, V, S> {
+    type Item = (&'a K, &'a mut V);
+    type IntoIter = hash_table::IterMut<'a, Option<(K, usize, V)>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        hash_table::IterMut::new(&mut self.table, self.size)
+    }
+}
+
 pub fn run() {
     let capacity: usize= 10;
-    let mut hash_table = LinearProbingRobinHoodHashTable::new(capacity);
+    // The identity hasher keeps keys on `key % capacity` so the layouts below are predictable.
+    let mut hash_table = LinearProbingRobinHoodHashTable::with_hasher(capacity, BuildIdentityHasher::default());
+    // Let the table fill up completely so the collisions below stay visible.
+    hash_table.set_max_load_factor(1.0);
 
     let one: Vec<char> = "one".chars().collect();
     let two: Vec<char> = "two".chars().collect();
@@ -196,36 +449,44 @@ pub fn run() {
     hash_table.print();
     assert!(hash_table.capacity == 10, "Capacity: {}", hash_table.capacity);
     assert!(hash_table.size == 9, "Size: {}", hash_table.size);
-    let mut return_val = hash_table.get(1);
+    let mut return_val = hash_table.get(&1);
     assert!(return_val.is_some());
     assert!(return_val.unwrap().iter().zip(one.clone()).filter(|&(a, b)| *a != b).count() == 0);
-    return_val = hash_table.get(2);
+    return_val = hash_table.get(&2);
     assert!(return_val.is_some());
     assert!(return_val.unwrap().iter().zip(two.clone()).filter(|&(a, b)| *a != b).count() == 0);
-    return_val = hash_table.get(3);
+    return_val = hash_table.get(&3);
     assert!(return_val.is_some());
     assert!(return_val.unwrap().iter().zip(three.clone()).filter(|&(a, b)| *a != b).count() == 0);
-    return_val = hash_table.get(4);
+    return_val = hash_table.get(&4);
     assert!(return_val.is_some());
     assert!(return_val.unwrap().iter().zip(four.clone()).filter(|&(a, b)| *a != b).count() == 0);
-    return_val = hash_table.get(5);
+    return_val = hash_table.get(&5);
     assert!(return_val.is_some());
     assert!(return_val.unwrap().iter().zip(five.clone()).filter(|&(a, b)| *a != b).count() == 0);
-    return_val = hash_table.get(6);
+    return_val = hash_table.get(&6);
     assert!(return_val.is_some());
     assert!(return_val.unwrap().iter().zip(six.clone()).filter(|&(a, b)| *a != b).count() == 0);
-    return_val = hash_table.get(7);
+    return_val = hash_table.get(&7);
     assert!(return_val.is_some());
     assert!(return_val.unwrap().iter().zip(seven.clone()).filter(|&(a, b)| *a != b).count() == 0);
-    return_val = hash_table.get(8);
+    return_val = hash_table.get(&8);
     assert!(return_val.is_some());
     assert!(return_val.unwrap().iter().zip(eight.clone()).filter(|&(a, b)| *a != b).count() == 0);
-    return_val = hash_table.get(11);
+    return_val = hash_table.get(&11);
     assert!(return_val.is_some());
     assert!(return_val.unwrap().iter().zip(eleven.clone()).filter(|&(a, b)| *a != b).count() == 0);
 
-    hash_table.delete(4);
-    return_val = hash_table.get(4);
+    // The same keys leave 11 eight buckets from its preferred one under plain linear probing. Here 11 and the
+    // keys it pushed back are each one bucket away, and a miss gives up after at most three probes.
+    let stats = hash_table.stats();
+    println!("Stats: {}", stats.to_json());
+    assert!(stats.robin_hood_offsets == Some(vec![1, 8]));
+    assert!(stats.hits.max == 2 && stats.hits.average() == 17.0 / 9.0);
+    assert!(stats.misses.max == 3 && stats.misses.average() == 2.7);
+
+    hash_table.remove(&4);
+    return_val = hash_table.get(&4);
     assert!(return_val.is_none());
     println!("After deleting key 4, keys 5 to 8 should be moved toward the front:");
     hash_table.print();
@@ -246,11 +507,11 @@ pub fn run() {
     hash_table.print();
     assert!(hash_table.size == 10);
 
-    hash_table.delete(12);
+    hash_table.remove(&12);
     println!("Deleted 12, keys 3 to 8 should be moved toward the front:");
     hash_table.print();
     assert!(hash_table.size == 9, "Size: {}", hash_table.size);
-    assert!(hash_table.get(12).is_none());
+    assert!(hash_table.get(&12).is_none());
 
     hash_table.insert(9, nine.clone());
     hash_table.insert(10, ten.clone());
@@ -269,25 +530,57 @@ pub fn run() {
     hash_table.print();
     assert!(hash_table.capacity == 20, "Capacity: {}", hash_table.capacity);
     assert!(hash_table.size == 17, "Size: {}", hash_table.size);
-    assert!(hash_table.get(15).is_some());
-    assert!(hash_table.get(35).is_some());
+    assert!(hash_table.get(&15).is_some());
+    assert!(hash_table.get(&35).is_some());
 
-    hash_table.delete(36);
+    hash_table.remove(&36);
     println!("Deleted 36, keys 16 & 56 should be moved toward the front:");
     hash_table.print();
     assert!(hash_table.capacity == 20, "Capacity: {}", hash_table.capacity);
     assert!(hash_table.size == 16, "Size: {}", hash_table.size);
-    assert!(hash_table.get(35).is_some());
-    assert!(hash_table.get(56).is_some());
-    assert!(hash_table.get(16).is_some());
-    assert!(hash_table.get(36).is_none());
+    assert!(hash_table.get(&35).is_some());
+    assert!(hash_table.get(&56).is_some());
+    assert!(hash_table.get(&16).is_some());
+    assert!(hash_table.get(&36).is_none());
 
-    hash_table.delete(35);
+    hash_table.remove(&35);
     println!("Deleted 35, keys 16 & 56 should be moved toward the front:");
     hash_table.print();
     assert!(hash_table.capacity == 20, "Capacity: {}", hash_table.capacity);
     assert!(hash_table.size == 15, "Size: {}", hash_table.size);
-    assert!(hash_table.get(36).is_none());
-    assert!(hash_table.get(56).is_some());
-    assert!(hash_table.get(16).is_some());
+    assert!(hash_table.get(&36).is_none());
+    assert!(hash_table.get(&56).is_some());
+    assert!(hash_table.get(&16).is_some());
+
+    // Keys 8, 18, 28, 38 & 48 all prefer bucket 8, so their cluster wraps around to the front of the table.
+    // Removing 8 shifts 28 back from bucket 0 to bucket 9, which retain hasn't visited yet.
+    let mut hash_table = LinearProbingRobinHoodHashTable::with_hasher(capacity, BuildIdentityHasher::default());
+    hash_table.set_max_load_factor(1.0);
+    for key in [8, 18, 28, 38, 48, 9, 0, 1] {
+        hash_table.insert(key, key);
+    }
+    println!("Before keeping only the keys from 10 up:");
+    hash_table.print();
+    let mut calls = 0;
+    hash_table.retain(|key, _| {
+        calls += 1;
+        *key >= 10
+    });
+    println!("After keeping only the keys from 10 up, 18 to 48 should be moved toward the front:");
+    hash_table.print();
+    assert!(calls == 8, "retain called f {} times", calls);
+    assert!(hash_table.size == 4, "Size: {}", hash_table.size);
+    assert!(hash_table.find_index_for_key_if_exists(&18) == Some(8));
+    assert!(hash_table.find_index_for_key_if_exists(&28) == Some(9));
+    for key in [18, 28, 38, 48] {
+        assert!(hash_table.get(&key) == Some(&key));
+    }
+    println!("All tests passed");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    hash_table::hash_table_tests!(LinearProbingRobinHoodHashTable);
 }
@@ -1,164 +1,444 @@
-struct LinearProbingTombstoneHashTable {
-    table: Vec<Option<(Option<usize>, Vec<char>)>>,
+use std::collections::hash_map::RandomState;
+use std::fmt::Debug;
+use std::hash::{BuildHasher, Hash};
+
+use crate::hash_table::{
+    self, BuildIdentityHasher, Entry, HashTable, HashTableStats, InsertAtBucket, MigrateBuckets, OccupiedEntry, Stats,
+    VacantEntry,
+};
+
+// Linear probing slows down sharply once clusters start merging, so keep a quarter of the buckets free.
+const DEFAULT_MAX_LOAD_FACTOR: f64 = 0.75;
+
+pub struct LinearProbingTombstoneHashTable<K, V, S = RandomState> {
+    // `None` is an empty bucket and `Some(None)` is a tombstone left behind by a deletion.
+    table: Vec<Option<Option<(K, V)>>>,
     capacity: usize,
     size: usize,
+    hash_builder: S,
+    max_load_factor: f64,
+    growth_factor: f64,
+    // When set, removals shrink the table once the load factor drops below this value.
+    min_load_factor: Option<f64>,
+    tombstones: usize,
 }
 
-const TOMBSTONE: Option<(Option<usize>, Vec<char>)> = Some((None, vec![]));
+impl<K: Hash + Eq, V> LinearProbingTombstoneHashTable<K, V> {
+    pub fn new(capacity: usize) -> Self {
+        Self::with_hasher(capacity, RandomState::new())
+    }
+}
 
-impl LinearProbingTombstoneHashTable {
-    fn new(capacity: usize) -> Self {
+impl<K: Hash + Eq, V, S: BuildHasher> LinearProbingTombstoneHashTable<K, V, S> {
+    pub fn with_hasher(capacity: usize, hash_builder: S) -> Self {
         LinearProbingTombstoneHashTable {
-            table: vec![None; capacity],
+            table: hash_table::empty_buckets(capacity),
             capacity,
             size: 0,
+            hash_builder,
+            max_load_factor: DEFAULT_MAX_LOAD_FACTOR,
+            growth_factor: hash_table::DEFAULT_GROWTH_FACTOR,
+            min_load_factor: None,
+            tombstones: 0,
+        }
+    }
+
+    /// The table grows before an insertion would take it above this fraction of its capacity.
+    pub fn set_max_load_factor(&mut self, max_load_factor: f64) {
+        hash_table::assert_valid_max_load_factor(max_load_factor);
+        hash_table::assert_valid_min_load_factor(self.min_load_factor, max_load_factor);
+        self.max_load_factor = max_load_factor;
+    }
+
+    /// Makes removals shrink the table once its load factor drops below `min_load_factor`.
+    /// `None`, the default, never shrinks automatically.
+    pub fn set_min_load_factor(&mut self, min_load_factor: Option<f64>) {
+        hash_table::assert_valid_min_load_factor(min_load_factor, self.max_load_factor);
+        self.min_load_factor = min_load_factor;
+    }
+
+    /// Factor the capacity is multiplied by whenever the table grows.
+    pub fn set_growth_factor(&mut self, growth_factor: f64) {
+        hash_table::assert_valid_growth_factor(growth_factor);
+        self.growth_factor = growth_factor;
+    }
+
+    /// Number of buckets holding a tombstone left behind by a removal.
+    pub fn tombstone_count(&self) -> usize {
+        self.tombstones
+    }
+
+    /// Rebuilds the table at its current capacity, dropping every tombstone. Live entries are moved out into
+    /// a temporary `Vec` and reinserted into the same bucket array, which is never reallocated.
+    pub fn compact(&mut self) {
+        let entries: Vec<(K, V)> = self.table
+            .iter_mut()
+            .filter_map(|bucket| bucket.take().flatten())
+            .collect();
+        self.size = 0;
+        self.tombstones = 0;
+        for (key, value) in entries {
+            self.insert(key, value);
         }
     }
 
-    fn get_preferred_index(&self, key: usize) -> usize {
-        key % self.capacity
+    fn get_preferred_index(&self, key: &K) -> usize {
+        (self.hash_builder.hash_one(key) % self.capacity as u64) as usize
     }
 
     fn resize_to(&mut self, new_capacity: usize) -> bool {
-        if new_capacity < self.capacity {
+        if !hash_table::fits_in_capacity(self.size, new_capacity, self.max_load_factor) {
             return false;
         }
         self.capacity = new_capacity;
-        let old_table = std::mem::take(&mut self.table);
-        self.table = vec![None; self.capacity];
+        let old_table = std::mem::replace(&mut self.table, hash_table::empty_buckets(self.capacity));
         self.size = 0;
+        self.tombstones = 0;
         for entry in old_table {
-            if let Some((key, value)) = entry {
-                if key.is_some() {
-                    self.insert(key.unwrap(), value);
-                }
+            if let Some(Some((key, value))) = entry {
+                self.insert(key, value);
             }
         }
-        return true;
+        true
     }
 
-    fn resize(&mut self) {
-        assert!(self.resize_to(self.capacity * 2));
+    // Shrinks the table if removals took it below the minimum load factor.
+    fn shrink_if_underloaded(&mut self) {
+        if let Some(min_load_factor) = self.min_load_factor
+            && (self.size as f64) < self.capacity as f64 * min_load_factor
+        {
+            self.shrink_to(hash_table::shrunk_capacity(self.size, min_load_factor, self.max_load_factor));
+        }
     }
 
-    fn insert(&mut self, key: usize, value: Vec<char>) {
-        // Resize the table because it's full.
-        if self.size >= self.capacity {
-            self.resize();
+    // Capacity to resize to before inserting one more entry, if the table has to be resized. Resizing to the
+    // current capacity compacts the table instead of growing it.
+    fn capacity_for_one_more(&self) -> Option<usize> {
+        if hash_table::exceeds_max_load_factor(self.size, self.capacity, self.max_load_factor) {
+            // Resize the table because it's getting too full.
+            Some(hash_table::grown_capacity(self.capacity, self.growth_factor))
+        } else if hash_table::exceeds_max_load_factor(
+            self.size + self.tombstones,
+            self.capacity,
+            self.max_load_factor,
+        ) {
+            // Tombstones lengthen probe sequences just like live entries do. If live entries only take up half
+            // of what the load factor allows, clearing the tombstones frees enough room; otherwise grow.
+            if hash_table::exceeds_max_load_factor(self.size, self.capacity, self.max_load_factor / 2.0) {
+                Some(hash_table::grown_capacity(self.capacity, self.growth_factor))
+            } else {
+                Some(self.capacity)
+            }
+        } else {
+            None
         }
+    }
+
+    // Makes room for one more entry. Called before probing, so that the bucket the probe picks for a new
+    // entry is still the right one when the entry is inserted.
+    fn reserve_one(&mut self) {
+        match self.capacity_for_one_more() {
+            Some(capacity) if capacity == self.capacity => self.compact(),
+            Some(capacity) => assert!(self.resize_to(capacity)),
+            None => {}
+        }
+    }
 
+    // Probes for `key` once. Returns `Ok` with the bucket holding the key, or `Err` with the bucket a new
+    // entry for the key goes into: the first tombstone passed on the way, or else the empty bucket that
+    // ended the probe.
+    fn find_bucket_for_insert(&mut self, key: &K) -> Result<usize, usize> {
         let preferred_index = self.get_preferred_index(key);
 
         let mut tombstone_found = false;
         let mut tombstone_idx: usize = 0;
 
-        // Loop until a free location is found.
-        // No need to check if the table is full during probing because the table is scaled as needed
-        // at the beginning of this method.
+        // No need to check if the table is full during probing because room for one more entry is made
+        // before probing.
         for i in 0..self.capacity {
             // Loop around to the front of the vector as needed.
             let probe_index = (preferred_index + i) % self.capacity;
 
-            if self.table[probe_index].is_none() {
-                if tombstone_found {
-                    // We hit an empty bucket, but we encountered a tombstone earlier
-                    self.table[tombstone_idx] = Some((Some(key), value));
-                } else {
+            match &self.table[probe_index] {
+                None => {
+                    if tombstone_found {
+                        // We hit an empty bucket, but we encountered a tombstone earlier
+                        return Err(tombstone_idx);
+                    }
                     // Empty bucket found and we didn't find a tombstone in the probing earlier
-                    // Insert at found empty bucket
-                    self.table[probe_index] = Some((Some(key), value));
+                    return Err(probe_index);
                 }
-                self.size += 1;
-                return;
-            } else if let Some((existing_key, _)) = &self.table[probe_index] {
-                if *existing_key == Some(key) {
-                    if tombstone_found {
-                        // Key already exists, but we found a tombstone earlier during probing
-                        // Move the entry to the earlier tombstone and set the current entry to a tombstone
-                        self.table[tombstone_idx] = Some((Some(key), value));
-                        self.table[probe_index] = TOMBSTONE;
-                    } else {
-                        // Key already exists and we didn't find a tombstone during probing, replace the value
-                        self.table[probe_index] = Some((Some(key), value));
+                Some(Some((existing_key, _))) => {
+                    if existing_key == key {
+                        if tombstone_found {
+                            // Key already exists, but we found a tombstone earlier during probing
+                            // Move the entry to the earlier tombstone and leave the tombstone in its place
+                            self.table.swap(tombstone_idx, probe_index);
+                            return Ok(tombstone_idx);
+                        }
+                        return Ok(probe_index);
+                    }
+                }
+                Some(None) => {
+                    if !tombstone_found {
+                        // This is the first tombstone we've found in probing; so record its position
+                        tombstone_found = true;
+                        tombstone_idx = probe_index;
                     }
-                    return;
-                } else if existing_key.is_none() && !tombstone_found {
-                    // This is the first tombstone we've found in probing; so record its position
-
-                    tombstone_found = true;
-                    tombstone_idx = probe_index;
                 }
             }
         }
+
+        // Every bucket was probed without finding the key or an empty bucket, so the table is all live
+        // entries and tombstones. Take the first tombstone.
+        assert!(tombstone_found);
+        Err(tombstone_idx)
     }
 
-    fn find_index_for_key_if_exists(&mut self, key: usize) -> Option<usize> {
-        let preferred_index = self.get_preferred_index(key);
+    fn find_index_for_key_if_exists(&self, key: &K) -> Option<usize> {
+        self.probe(self.get_preferred_index(key), |existing_key| existing_key == key).0
+    }
 
+    // Probes from `preferred_index` until `is_match` accepts a key or probing reaches an empty bucket. Returns
+    // the bucket of the accepted key, if any, and the number of buckets inspected.
+    fn probe(&self, preferred_index: usize, is_match: impl Fn(&K) -> bool) -> (Option<usize>, usize) {
         for i in 0..self.capacity {
             let probe_index: usize = (preferred_index + i) % self.capacity;
 
-            if let Some((existing_key, _)) = &self.table[probe_index] {
+            match &self.table[probe_index] {
                 // We've hit an occupied bucket. Check if the key matches, in which case, we have a hit.
                 // If the key doesn't match, continue probing by jumping to the line incrementing i.
-                if *existing_key == Some(key) {
-                    return Some(probe_index);
+                Some(Some((existing_key, _))) => {
+                    if is_match(existing_key) {
+                        return (Some(probe_index), i + 1);
+                    }
                 }
-            } else {
+                // Tombstones don't end the probe sequence.
+                Some(None) => {}
                 // We've hit an empty bucket during probing.
-                return None;
+                None => return (None, i + 1),
             }
         }
         // Table is full, key not found after wrap-around probing
-        return None;
+        (None, self.capacity)
+    }
+}
+
+impl<K: Debug, V: Debug, S> LinearProbingTombstoneHashTable<K, V, S> {
+    pub fn print(&self) {
+        println!("Capacity: {}", self.capacity);
+        println!("Size: {}", self.size);
+        println!("Tombstones: {}", self.tombstones);
+        println!("Entries:");
+        for entry in &self.table {
+            match entry {
+                Some(Some((key, value))) => println!("\t{:?}: {:?}", key, value),
+                Some(None) => println!("\t<TOMBSTONE>"),
+                None => println!("\t<None>"),
+            }
+        }
+    }
+}
 
+impl<K: Hash + Eq, V, S: BuildHasher> HashTable<K, V> for LinearProbingTombstoneHashTable<K, V, S> {
+    fn len(&self) -> usize {
+        self.size
     }
 
-    fn get(&mut self, key: usize) -> Option<&Vec<char>> {
-        let found_index = self.find_index_for_key_if_exists(key);
-        if found_index.is_none() {
-            return None;
+    fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    fn entry(&mut self, key: K) -> Entry<'_, K, V> {
+        self.reserve_one();
+        match self.find_bucket_for_insert(&key) {
+            Ok(index) => Entry::Occupied(OccupiedEntry::from_bucket(&mut self.table[index])),
+            Err(index) => Entry::Vacant(VacantEntry::new(key, index, self)),
         }
-        if let Some((_, value)) = &self.table[found_index.unwrap()] {
-            return Some(value);
+    }
+
+    fn get_key_value(&self, key: &K) -> Option<(&K, &V)> {
+        let found_index = self.find_index_for_key_if_exists(key)?;
+        if let Some(Some((key, value))) = &self.table[found_index] {
+            Some((key, value))
         } else {
-            return None;
+            None
         }
     }
 
-    fn delete(&mut self, key: usize) {
-        let found_index = self.find_index_for_key_if_exists(key);
-        if found_index.is_none() {
-            return;
+    fn get_mut(&mut self, key: &K) -> Option<&mut V> {
+        let found_index = self.find_index_for_key_if_exists(key)?;
+        if let Some(Some((_, value))) = &mut self.table[found_index] {
+            Some(value)
+        } else {
+            None
         }
-        let actual_index = found_index.unwrap();
-        if let Some((_, _)) = &self.table[actual_index] {
-            self.size -= 1;
-            self.table[actual_index] = TOMBSTONE;
+    }
+
+    fn get_many_mut<const N: usize>(&mut self, keys: [&K; N]) -> Option<[&mut V; N]> {
+        let mut indices = [0; N];
+        for (index, key) in indices.iter_mut().zip(keys) {
+            *index = self.find_index_for_key_if_exists(key)?;
         }
+        hash_table::values_at_disjoint_buckets(&mut self.table, indices)
     }
 
-    fn print(&self) {
-        println!("Capacity: {}", self.capacity);
-        println!("Size: {}", self.size);
-        println!("Entries:");
-        for entry in &self.table {
-            if let Some((key, value)) = entry {
-                if key.is_none() {
-                    println!("\t<TOMBSTONE>");
-                } else {
-                    println!("\t{}: {}", key.unwrap(), value.iter().collect::<String>());
-                }
-            } else {
-                println!("\t<None>");
+    fn remove_entry(&mut self, key: &K) -> Option<(K, V)> {
+        let found_index = self.find_index_for_key_if_exists(key)?;
+        self.size -= 1;
+        self.tombstones += 1;
+        let entry = self.table[found_index].replace(None).flatten();
+        self.shrink_if_underloaded();
+        entry
+    }
+
+    fn retain<F: FnMut(&K, &mut V) -> bool>(&mut self, mut f: F) {
+        for bucket in &mut self.table {
+            if let Some(Some((key, value))) = bucket
+                && !f(key, value)
+            {
+                *bucket = Some(None);
+                self.size -= 1;
+                self.tombstones += 1;
             }
         }
+        self.shrink_if_underloaded();
+    }
+
+    fn clear(&mut self) {
+        self.table = hash_table::empty_buckets(self.capacity);
+        self.size = 0;
+        self.tombstones = 0;
+    }
+
+    fn iter<'a>(&'a self) -> impl ExactSizeIterator<Item = (&'a K, &'a V)>
+    where
+        K: 'a,
+        V: 'a,
+    {
+        hash_table::Iter::new(&self.table, self.size)
+    }
+
+    fn iter_mut<'a>(&'a mut self) -> impl ExactSizeIterator<Item = (&'a K, &'a mut V)>
+    where
+        K: 'a,
+        V: 'a,
+    {
+        hash_table::IterMut::new(&mut self.table, self.size)
+    }
+
+    fn drain(&mut self) -> impl ExactSizeIterator<Item = (K, V)> {
+        let len = self.size;
+        self.size = 0;
+        self.tombstones = 0;
+        hash_table::Drain::new(&mut self.table, len)
+    }
+
+    fn shrink_to(&mut self, min_capacity: usize) {
+        let new_capacity = min_capacity.max(hash_table::min_capacity_for(self.size, self.max_load_factor));
+        if new_capacity < self.capacity {
+            assert!(self.resize_to(new_capacity));
+        }
+    }
+}
+
+impl<K, V, S> InsertAtBucket<K, V> for LinearProbingTombstoneHashTable<K, V, S> {
+    fn insert_at_bucket(&mut self, index: usize, key: K, value: V) -> &mut V {
+        if self.table[index].is_some() {
+            // The bucket holds a tombstone that is being reused.
+            self.tombstones -= 1;
+        }
+        self.size += 1;
+        match self.table[index].insert(Some((key, value))) {
+            Some((_, value)) => value,
+            None => unreachable!(),
+        }
+    }
+}
+
+impl<K: Hash + Eq, V, S: BuildHasher + Clone> MigrateBuckets<K, V> for LinearProbingTombstoneHashTable<K, V, S> {
+    fn capacity_to_resize_to(&self) -> Option<usize> {
+        self.capacity_for_one_more()
+    }
+
+    fn capacity_for(&self, len: usize) -> usize {
+        hash_table::min_capacity_for(len, self.max_load_factor)
+    }
+
+    fn empty_with_capacity(&self, capacity: usize) -> Self {
+        let mut hash_table = Self::with_hasher(capacity, self.hash_builder.clone());
+        hash_table.max_load_factor = self.max_load_factor;
+        hash_table.growth_factor = self.growth_factor;
+        hash_table.min_load_factor = self.min_load_factor;
+        hash_table
+    }
+
+    fn bucket_count(&self) -> usize {
+        self.table.len()
+    }
+
+    fn bucket_of(&self, key: &K) -> Option<usize> {
+        self.find_index_for_key_if_exists(key)
+    }
+
+    fn take_bucket(&mut self, index: usize) -> impl Iterator<Item = (K, V)> {
+        // Leave a tombstone, so that probes for the remaining keys still go past this bucket.
+        let entry = self.table[index].as_mut().and_then(Option::take);
+        if entry.is_some() {
+            self.size -= 1;
+            self.tombstones += 1;
+        }
+        entry.into_iter()
+    }
+}
+
+impl<K: Hash + Eq, V, S: BuildHasher> HashTableStats for LinearProbingTombstoneHashTable<K, V, S> {
+    fn stats(&self) -> Stats {
+        let hits = self.keys().map(|key| {
+            self.probe(self.get_preferred_index(key), |existing_key| existing_key == key).1
+        });
+        // A miss walks from its preferred bucket to the next empty one, whatever the key.
+        let misses = (0..self.capacity).map(|index| self.probe(index, |_| false).1);
+        let mut stats = Stats::new(self.size, self.capacity, hits.collect(), misses.collect());
+        stats.tombstones = self.tombstones;
+        stats.longest_cluster = hash_table::longest_cluster(&self.table);
+        stats
+    }
+}
+
+impl<K, V, S> IntoIterator for LinearProbingTombstoneHashTable<K, V, S> {
+    type Item = (K, V);
+    type IntoIter = hash_table::IntoIter<Option<Option<(K, V)>>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        hash_table::IntoIter::new(self.table, self.size)
+    }
+}
+
+impl<'a, K, V, S> IntoIterator for &'a LinearProbingTombstoneHashTable<K, V, S> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = hash_table::Iter<'a, Option<Option<(K, V)>>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        hash_table::Iter::new(&self.table, self.size)
+    }
+}
+
+impl<'a, K, V, S> IntoIterator for &'a mut LinearProbingTombstoneHashTable<K, V, S> {
+    type Item = (&'a K, &'a mut V);
+    type IntoIter = hash_table::IterMut<'a, Option<Option<(K, V)>>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        hash_table::IterMut::new(&mut self.table, self.size)
     }
 }
 
 pub fn run() {
     let capacity: usize= 10;
-    let mut hash_table = LinearProbingTombstoneHashTable::new(capacity);
+    // The identity hasher keeps keys on `key % capacity` so the layouts below are predictable.
+    let mut hash_table = LinearProbingTombstoneHashTable::with_hasher(capacity, BuildIdentityHasher::default());
+    // Let the table fill up completely so the collisions below stay visible.
+    hash_table.set_max_load_factor(1.0);
 
     let one: Vec<char> = "one".chars().collect();
     let two: Vec<char> = "two".chars().collect();
@@ -187,50 +467,62 @@ pub fn run() {
     hash_table.print();
     assert!(hash_table.capacity == 10);
     assert!(hash_table.size == 9);
-    let mut return_val = hash_table.get(1);
+    let mut return_val = hash_table.get(&1);
     assert!(return_val.is_some());
     assert!(return_val.unwrap().iter().zip(one.clone()).filter(|&(a, b)| *a != b).count() == 0);
-    return_val = hash_table.get(2);
+    return_val = hash_table.get(&2);
     assert!(return_val.is_some());
     assert!(return_val.unwrap().iter().zip(two.clone()).filter(|&(a, b)| *a != b).count() == 0);
-    return_val = hash_table.get(3);
+    return_val = hash_table.get(&3);
     assert!(return_val.is_some());
     assert!(return_val.unwrap().iter().zip(three.clone()).filter(|&(a, b)| *a != b).count() == 0);
-    return_val = hash_table.get(4);
+    return_val = hash_table.get(&4);
     assert!(return_val.is_some());
     assert!(return_val.unwrap().iter().zip(four.clone()).filter(|&(a, b)| *a != b).count() == 0);
-    return_val = hash_table.get(5);
+    return_val = hash_table.get(&5);
     assert!(return_val.is_some());
     assert!(return_val.unwrap().iter().zip(five.clone()).filter(|&(a, b)| *a != b).count() == 0);
-    return_val = hash_table.get(6);
+    return_val = hash_table.get(&6);
     assert!(return_val.is_some());
     assert!(return_val.unwrap().iter().zip(six.clone()).filter(|&(a, b)| *a != b).count() == 0);
-    return_val = hash_table.get(7);
+    return_val = hash_table.get(&7);
     assert!(return_val.is_some());
     assert!(return_val.unwrap().iter().zip(seven.clone()).filter(|&(a, b)| *a != b).count() == 0);
-    return_val = hash_table.get(8);
+    return_val = hash_table.get(&8);
     assert!(return_val.is_some());
     assert!(return_val.unwrap().iter().zip(eight.clone()).filter(|&(a, b)| *a != b).count() == 0);
-    return_val = hash_table.get(11);
+    return_val = hash_table.get(&11);
     assert!(return_val.is_some());
     assert!(return_val.unwrap().iter().zip(eleven.clone()).filter(|&(a, b)| *a != b).count() == 0);
 
-    hash_table.delete(4);
-    return_val = hash_table.get(4);
+    // 11 sits 8 buckets past its preferred one, and a miss starting anywhere in the cluster has to walk to
+    // bucket 0, the only empty one.
+    let stats = hash_table.stats();
+    println!("Stats: {}", stats.to_json());
+    assert!(stats.hits.histogram == [0, 8, 0, 0, 0, 0, 0, 0, 0, 1]);
+    assert!(stats.hits.average() == 17.0 / 9.0);
+    assert!(stats.misses.max == 10 && stats.misses.average() == 5.5);
+    assert!(stats.longest_cluster == 9);
+
+    hash_table.remove(&4);
+    return_val = hash_table.get(&4);
     assert!(return_val.is_none());
     println!("After deleting key 4:");
     hash_table.print();
     assert!(hash_table.size == 8);
+    assert!(hash_table.tombstones == 1);
 
     hash_table.insert(11, eleven.clone());
     println!("Re-inserted 11, which should be moved to the earlier tombstone:");
     hash_table.print();
     assert!(hash_table.size == 8);
+    assert!(hash_table.tombstones == 1);
 
     hash_table.insert(12, twelve.clone());
-    println!("Inserted 12, which should collide with 2:");
+    println!("Inserted 12, which should collide with 2 and take the tombstone 11 left behind:");
     hash_table.print();
     assert!(hash_table.size == 9);
+    assert!(hash_table.tombstones == 0);
 
     hash_table.insert(9, nine.clone());
     hash_table.insert(10, ten.clone());
@@ -238,4 +530,21 @@ pub fn run() {
     hash_table.print();
     assert!(hash_table.capacity == 20);
     assert!(hash_table.size == 11);
+    println!("All tests passed");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    hash_table::hash_table_tests!(LinearProbingTombstoneHashTable);
+
+    #[test]
+    fn compaction() {
+        hash_table::check_compaction(
+            &mut LinearProbingTombstoneHashTable::new(10),
+            LinearProbingTombstoneHashTable::tombstone_count,
+            LinearProbingTombstoneHashTable::compact,
+        );
+    }
 }
@@ -1,22 +1,21 @@
 use std::env;
 
-mod print_time;
-mod menu;
-mod kadane;
-mod common_chars;
-mod lpts_hash_table;
-mod lprh_hash_table;
-mod qpts_hash_table;
-mod dhts_hash_table;
-mod cuckoo_hash_table;
-mod bloom_filter;
-mod counting_bloom_filter;
+use alg::{
+    bloom_filter, bucketized_cuckoo_hash_table, common_chars, counting_bloom_filter, cuckoo_hash_table, dhts_hash_table,
+    hopscotch_hash_table, incremental_hash_table, kadane, lprh_hash_table, lpts_hash_table, menu, qpts_hash_table,
+    sc_hash_table, swiss_hash_table,
+};
 
+// Checks and benchmarks that only the binary runs, kept out of the library's public API.
+mod differential_testing;
+mod large_value_benchmark;
 
 fn main() {
     let all_algs = [
         "menu", "kadane", "common_chars", "lpts_hash_table", "lprh_hash_table", "qpts_hash_table",
-        "cuckoo_hash_table",
+        "dhts_hash_table", "cuckoo_hash_table", "bucketized_cuckoo_hash_table", "hopscotch_hash_table",
+        "sc_hash_table", "swiss_hash_table", "incremental_hash_table", "differential_testing",
+        "large_value_benchmark", "bloom_filter", "counting_bloom_filter",
     ];
     let args: Vec<_> = env::args().collect();
     if args.len() != 2 {
@@ -42,6 +41,20 @@ fn main() {
         dhts_hash_table::run();
     } else if alg_name == "cuckoo_hash_table" {
         cuckoo_hash_table::run();
+    } else if alg_name == "bucketized_cuckoo_hash_table" {
+        bucketized_cuckoo_hash_table::run();
+    } else if alg_name == "hopscotch_hash_table" {
+        hopscotch_hash_table::run();
+    } else if alg_name == "sc_hash_table" {
+        sc_hash_table::run();
+    } else if alg_name == "swiss_hash_table" {
+        swiss_hash_table::run();
+    } else if alg_name == "incremental_hash_table" {
+        incremental_hash_table::run();
+    } else if alg_name == "differential_testing" {
+        differential_testing::run();
+    } else if alg_name == "large_value_benchmark" {
+        large_value_benchmark::run();
     } else if alg_name == "bloom_filter" {
         bloom_filter::run();
     } else if alg_name == "counting_bloom_filter" {
@@ -1,17 +1,18 @@
 use crate::print_time;
 
-fn menu(arr: &[f64], target: f64) -> bool {
+/// Whether `target` can be made by summing items of `arr`, each as many times as needed.
+pub fn menu(arr: &[f64], target: f64) -> bool {
     let arr_len = arr.len();
     if arr_len == 0 { return false; }
     let next_item = arr[0];
     if target % next_item == 0.0 || (
-        next_item < target && menu(&arr, target - next_item)
+        next_item < target && menu(arr, target - next_item)
     ) { return true; }
-    return menu(&arr[1..], target);
+    menu(&arr[1..], target)
 }
 
 fn print_menu_run(arr: &[f64], target: f64) {
-    println!("{}", menu(&arr, target));
+    println!("{}", menu(arr, target));
 }
 
 pub fn run() {
@@ -2,10 +2,10 @@ use std::time::Instant;
 
 pub fn print_time(prev_inst: Option<Instant>) -> Instant {
     let now: Instant = Instant::now();
-    if prev_inst == None {
-        now
-    } else {
-        println!("{:?}", now.duration_since(prev_inst.unwrap()));
+    if let Some(prev_inst) = prev_inst {
+        println!("{:?}", now.duration_since(prev_inst));
         Instant::now()
+    } else {
+        now
     }
 }
@@ -1,269 +1,546 @@
-struct QuadraticProbingTombstoneHashTable {
-    table: Vec<Option<(Option<usize>, Vec<char>)>>,
+use std::collections::hash_map::RandomState;
+use std::fmt::Debug;
+use std::hash::{BuildHasher, Hash};
+
+use crate::hash_table::{
+    self, BuildIdentityHasher, Entry, HashTable, HashTableStats, InsertAtBucket, MigrateBuckets, OccupiedEntry, Stats,
+    VacantEntry,
+};
+
+// Triangular probing reaches every bucket, so any load factor works, but probe sequences stay short while the
+// table is at most half full.
+const DEFAULT_MAX_LOAD_FACTOR: f64 = 0.5;
+
+pub struct QuadraticProbingTombstoneHashTable<K, V, S = RandomState> {
+    // `None` is an empty bucket and `Some(None)` is a tombstone left behind by a deletion.
+    table: Vec<Option<Option<(K, V)>>>,
     capacity: usize,
     size: usize,
-    c2: usize,
-    c1: usize,
-    c0: usize,
+    hash_builder: S,
+    max_load_factor: f64,
+    growth_factor: f64,
+    // When set, removals shrink the table once the load factor drops below this value.
+    min_load_factor: Option<f64>,
+    tombstones: usize,
 }
 
-const TOMBSTONE: Option<(Option<usize>, Vec<char>)> = Some((None, vec![]));
+impl<K: Hash + Eq, V> QuadraticProbingTombstoneHashTable<K, V> {
+    pub fn new(capacity: usize) -> Self {
+        Self::with_hasher(capacity, RandomState::new())
+    }
+}
 
-impl QuadraticProbingTombstoneHashTable {
-    fn new(capacity: usize, c2: usize, c1: usize, c0: usize) -> Self {
+impl<K: Hash + Eq, V, S: BuildHasher> QuadraticProbingTombstoneHashTable<K, V, S> {
+    /// The capacity is rounded up to a power of two, where triangular probing visits every bucket.
+    pub fn with_hasher(capacity: usize, hash_builder: S) -> Self {
+        let capacity = capacity.next_power_of_two();
         QuadraticProbingTombstoneHashTable {
-            table: vec![None; capacity],
+            table: hash_table::empty_buckets(capacity),
             capacity,
             size: 0,
-            c2,
-            c1,
-            c0,
+            hash_builder,
+            max_load_factor: DEFAULT_MAX_LOAD_FACTOR,
+            growth_factor: hash_table::DEFAULT_GROWTH_FACTOR,
+            min_load_factor: None,
+            tombstones: 0,
         }
     }
 
-    fn get_preferred_index(&self, key: usize) -> usize {
-        // For testing simplicity, we just mod the key by the capacity.
-        // We would ideally use a proper hash function here.
-        key % self.capacity
+    /// The table grows before an insertion would take it above this fraction of its capacity.
+    pub fn set_max_load_factor(&mut self, max_load_factor: f64) {
+        hash_table::assert_valid_max_load_factor(max_load_factor);
+        hash_table::assert_valid_min_load_factor(self.min_load_factor, max_load_factor);
+        self.max_load_factor = max_load_factor;
     }
 
-    fn get_probe_index(&self, preferred_index: usize, i: usize) -> usize {
-        if i > 0 {
-            (preferred_index + (self.c2 * i * i) + (self.c1 * i) + self.c0) % self.capacity
-        } else {
-            preferred_index
+    /// Makes removals shrink the table once its load factor drops below `min_load_factor`.
+    /// `None`, the default, never shrinks automatically.
+    pub fn set_min_load_factor(&mut self, min_load_factor: Option<f64>) {
+        hash_table::assert_valid_min_load_factor(min_load_factor, self.max_load_factor);
+        self.min_load_factor = min_load_factor;
+    }
+
+    /// Factor the capacity is multiplied by whenever the table grows.
+    pub fn set_growth_factor(&mut self, growth_factor: f64) {
+        hash_table::assert_valid_growth_factor(growth_factor);
+        self.growth_factor = growth_factor;
+    }
+
+    /// Number of buckets holding a tombstone left behind by a removal.
+    pub fn tombstone_count(&self) -> usize {
+        self.tombstones
+    }
+
+    /// Rebuilds the table at its current capacity, dropping every tombstone. Live entries are moved out into
+    /// a temporary `Vec` and reinserted into the same bucket array, which is never reallocated.
+    pub fn compact(&mut self) {
+        let entries: Vec<(K, V)> = self.table
+            .iter_mut()
+            .filter_map(|bucket| bucket.take().flatten())
+            .collect();
+        self.size = 0;
+        self.tombstones = 0;
+        for (key, value) in entries {
+            self.insert(key, value);
         }
     }
 
+    fn get_preferred_index(&self, key: &K) -> usize {
+        (self.hash_builder.hash_one(key) % self.capacity as u64) as usize
+    }
+
+    // Offsets by the triangular numbers i * (i + 1) / 2, which modulo a power of two are a permutation of the
+    // buckets: the first `capacity` probes visit each bucket exactly once. Integer coefficients like
+    // i^2 + i + 1 only ever reach half the buckets of an even capacity.
+    fn get_probe_index(&self, preferred_index: usize, i: usize) -> usize {
+        (preferred_index + i * (i + 1) / 2) % self.capacity
+    }
+
     fn resize_to(&mut self, new_capacity: usize) -> bool {
-        if new_capacity < self.capacity {
+        debug_assert!(new_capacity.is_power_of_two(), "Capacity {} isn't a power of two", new_capacity);
+        if !hash_table::fits_in_capacity(self.size, new_capacity, self.max_load_factor) {
             return false;
         }
         self.capacity = new_capacity;
-        let old_table = std::mem::take(&mut self.table);
-        self.table = vec![None; self.capacity];
+        let old_table = std::mem::replace(&mut self.table, hash_table::empty_buckets(self.capacity));
         self.size = 0;
+        self.tombstones = 0;
         for entry in old_table {
-            if let Some((key, value)) = entry {
-                if key.is_some() {
-                    self.insert(key.unwrap(), value);
-                }
+            if let Some(Some((key, value))) = entry {
+                self.insert(key, value);
             }
         }
-        return true;
+        true
     }
 
-    fn resize(&mut self) {
-        assert!(self.resize_to(self.capacity * 2));
+    // Shrinks the table if removals took it below the minimum load factor.
+    fn shrink_if_underloaded(&mut self) {
+        if let Some(min_load_factor) = self.min_load_factor
+            && (self.size as f64) < self.capacity as f64 * min_load_factor
+        {
+            self.shrink_to(hash_table::shrunk_capacity(self.size, min_load_factor, self.max_load_factor));
+        }
     }
 
-    fn insert(&mut self, key: usize, value: Vec<char>) {
-        // Resize the table because it's full.
-        if self.size >= self.capacity {
-            self.resize();
+    fn grown_capacity(&self) -> usize {
+        hash_table::grown_capacity(self.capacity, self.growth_factor).next_power_of_two()
+    }
+
+    // Capacity to resize to before inserting one more entry, if the table has to be resized. Resizing to the
+    // current capacity compacts the table instead of growing it.
+    fn capacity_for_one_more(&self) -> Option<usize> {
+        if hash_table::exceeds_max_load_factor(self.size, self.capacity, self.max_load_factor) {
+            // Resize the table because it's getting too full.
+            Some(self.grown_capacity())
+        } else if hash_table::exceeds_max_load_factor(
+            self.size + self.tombstones,
+            self.capacity,
+            self.max_load_factor,
+        ) {
+            // Tombstones lengthen probe sequences just like live entries do. If live entries only take up half
+            // of what the load factor allows, clearing the tombstones frees enough room; otherwise grow.
+            if hash_table::exceeds_max_load_factor(self.size, self.capacity, self.max_load_factor / 2.0) {
+                Some(self.grown_capacity())
+            } else {
+                Some(self.capacity)
+            }
+        } else {
+            None
         }
+    }
+
+    // Makes room for one more entry. Called before probing, so that the bucket the probe picks for a new
+    // entry is still the right one when the entry is inserted.
+    fn reserve_one(&mut self) {
+        match self.capacity_for_one_more() {
+            Some(capacity) if capacity == self.capacity => self.compact(),
+            Some(capacity) => assert!(self.resize_to(capacity)),
+            None => {}
+        }
+    }
 
-        // Try to resize 4 times and try probing again if the inner loop fails.
-        for _ in 0..3 {
-            let preferred_index = self.get_preferred_index(key);
-            let mut tombstone_found = false;
-            let mut tombstone_idx: usize = 0;
+    // Probes for `key` once. Returns `Ok` with the bucket holding the key, or `Err` with the bucket a new
+    // entry for the key goes into: the first tombstone passed on the way, or else the empty bucket that
+    // ended the probe. Returns `None` if the probe sequence visited neither, which only happens when every
+    // bucket holds a live entry.
+    fn find_bucket_for_insert(&mut self, key: &K) -> Option<Result<usize, usize>> {
+        let preferred_index = self.get_preferred_index(key);
+
+        let mut tombstone_found = false;
+        let mut tombstone_idx: usize = 0;
 
-            // Loop until a free location is found.
-            for i in 0..self.capacity {
-                // Loop around to the front of the vector as needed.
-                let probe_index = self.get_probe_index(preferred_index, i);
+        for i in 0..self.capacity {
+            // Loop around to the front of the vector as needed.
+            let probe_index = self.get_probe_index(preferred_index, i);
 
-                if self.table[probe_index].is_none() {
+            match &self.table[probe_index] {
+                None => {
                     if tombstone_found {
                         // We hit an empty bucket, but we encountered a tombstone earlier
-                        self.table[tombstone_idx] = Some((Some(key), value));
-                    } else {
-                        // Empty bucket found and we didn't find a tombstone in the probing earlier
-                        // Insert at found empty bucket
-                        self.table[probe_index] = Some((Some(key), value));
+                        return Some(Err(tombstone_idx));
                     }
-                    self.size += 1;
-                    return;
-                } else if let Some((existing_key, _)) = &self.table[probe_index] {
-                    if *existing_key == Some(key) {
+                    // Empty bucket found and we didn't find a tombstone in the probing earlier
+                    return Some(Err(probe_index));
+                }
+                Some(Some((existing_key, _))) => {
+                    if existing_key == key {
                         if tombstone_found {
                             // Key already exists, but we found a tombstone earlier during probing
-                            // Move the entry to the earlier tombstone and set the current entry to a tombstone
-                            self.table[tombstone_idx] = Some((Some(key), value));
-                            self.table[probe_index] = TOMBSTONE;
-                        } else {
-                            // Key already exists and we didn't find a tombstone during probing, replace the value
-                            self.table[probe_index] = Some((Some(key), value));
+                            // Move the entry to the earlier tombstone and leave the tombstone in its place
+                            self.table.swap(tombstone_idx, probe_index);
+                            return Some(Ok(tombstone_idx));
                         }
-                        return;
-                    } else if existing_key.is_none() && !tombstone_found {
+                        return Some(Ok(probe_index));
+                    }
+                }
+                Some(None) => {
+                    if !tombstone_found {
                         // This is the first tombstone we've found in probing; so record its position
-
                         tombstone_found = true;
                         tombstone_idx = probe_index;
                     }
                 }
             }
+        }
 
-            // Could not find a bucket for insertion. Resize and try again.
-            self.resize();
+        if tombstone_found {
+            // The key isn't in the table and probing didn't reach an empty bucket, but it did pass a
+            // tombstone that can be reused.
+            return Some(Err(tombstone_idx));
         }
-        panic!("Could not find a place to insert key {} after resizing 4 times", key);
+        None
     }
 
-    fn find_index_for_key_if_exists(&mut self, key: usize) -> Option<usize> {
-        let preferred_index = self.get_preferred_index(key);
+    fn find_index_for_key_if_exists(&self, key: &K) -> Option<usize> {
+        self.probe(self.get_preferred_index(key), |existing_key| existing_key == key).0
+    }
 
+    // Probes from `preferred_index` until `is_match` accepts a key or probing reaches an empty bucket. Returns
+    // the bucket of the accepted key, if any, and the number of buckets inspected.
+    fn probe(&self, preferred_index: usize, is_match: impl Fn(&K) -> bool) -> (Option<usize>, usize) {
         for i in 0..self.capacity {
             let probe_index: usize = self.get_probe_index(preferred_index, i);
 
-            if let Some((existing_key, _)) = &self.table[probe_index] {
+            match &self.table[probe_index] {
                 // We've hit an occupied bucket. Check if the key matches, in which case, we have a hit.
                 // If the key doesn't match, continue probing by jumping to the line incrementing i.
-                if *existing_key == Some(key) {
-                    return Some(probe_index);
+                Some(Some((existing_key, _))) => {
+                    if is_match(existing_key) {
+                        return (Some(probe_index), i + 1);
+                    }
                 }
-            } else {
+                // Tombstones don't end the probe sequence.
+                Some(None) => {}
                 // We've hit an empty bucket during probing.
-                return None;
+                None => return (None, i + 1),
             }
         }
         // Table is full, key not found after wrap-around probing
-        return None;
+        (None, self.capacity)
+    }
+}
 
+impl<K: Debug, V: Debug, S> QuadraticProbingTombstoneHashTable<K, V, S> {
+    pub fn print(&self) {
+        println!("Capacity: {}", self.capacity);
+        println!("Size: {}", self.size);
+        println!("Tombstones: {}", self.tombstones);
+        println!("Entries:");
+        for entry in &self.table {
+            match entry {
+                Some(Some((key, value))) => println!("\t{:?}: {:?}", key, value),
+                Some(None) => println!("\t<TOMBSTONE>"),
+                None => println!("\t<None>"),
+            }
+        }
     }
+}
 
-    fn get(&mut self, key: usize) -> Option<&Vec<char>> {
-        let found_index = self.find_index_for_key_if_exists(key);
-        if found_index.is_none() {
-            return None;
+impl<K: Hash + Eq, V, S: BuildHasher> HashTable<K, V> for QuadraticProbingTombstoneHashTable<K, V, S> {
+    fn len(&self) -> usize {
+        self.size
+    }
+
+    fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    fn entry(&mut self, key: K) -> Entry<'_, K, V> {
+        self.reserve_one();
+        // The probe visits every bucket, and `reserve_one` left at least one without a live entry.
+        match self.find_bucket_for_insert(&key) {
+            Some(Ok(index)) => Entry::Occupied(OccupiedEntry::from_bucket(&mut self.table[index])),
+            Some(Err(index)) => Entry::Vacant(VacantEntry::new(key, index, self)),
+            None => unreachable!("Probing found no bucket for a new entry"),
         }
-        if let Some((_, value)) = &self.table[found_index.unwrap()] {
-            return Some(value);
+    }
+
+    fn get_key_value(&self, key: &K) -> Option<(&K, &V)> {
+        let found_index = self.find_index_for_key_if_exists(key)?;
+        if let Some(Some((key, value))) = &self.table[found_index] {
+            Some((key, value))
         } else {
-            return None;
+            None
         }
     }
 
-    fn delete(&mut self, key: usize) {
-        let found_index = self.find_index_for_key_if_exists(key);
-        if found_index.is_none() {
-            return;
+    fn get_mut(&mut self, key: &K) -> Option<&mut V> {
+        let found_index = self.find_index_for_key_if_exists(key)?;
+        if let Some(Some((_, value))) = &mut self.table[found_index] {
+            Some(value)
+        } else {
+            None
         }
-        let actual_index = found_index.unwrap();
-        if let Some((_, _)) = &self.table[actual_index] {
-            self.size -= 1;
-            self.table[actual_index] = TOMBSTONE;
+    }
+
+    fn get_many_mut<const N: usize>(&mut self, keys: [&K; N]) -> Option<[&mut V; N]> {
+        let mut indices = [0; N];
+        for (index, key) in indices.iter_mut().zip(keys) {
+            *index = self.find_index_for_key_if_exists(key)?;
         }
+        hash_table::values_at_disjoint_buckets(&mut self.table, indices)
     }
 
-    fn print(&self) {
-        println!("Capacity: {}", self.capacity);
-        println!("Size: {}", self.size);
-        println!("Entries:");
-        for entry in &self.table {
-            if let Some((key, value)) = entry {
-                if key.is_none() {
-                    println!("\t<TOMBSTONE>");
-                } else {
-                    println!("\t{}: {}", key.unwrap(), value.iter().collect::<String>());
-                }
-            } else {
-                println!("\t<None>");
+    fn remove_entry(&mut self, key: &K) -> Option<(K, V)> {
+        let found_index = self.find_index_for_key_if_exists(key)?;
+        self.size -= 1;
+        self.tombstones += 1;
+        let entry = self.table[found_index].replace(None).flatten();
+        self.shrink_if_underloaded();
+        entry
+    }
+
+    fn retain<F: FnMut(&K, &mut V) -> bool>(&mut self, mut f: F) {
+        for bucket in &mut self.table {
+            if let Some(Some((key, value))) = bucket
+                && !f(key, value)
+            {
+                *bucket = Some(None);
+                self.size -= 1;
+                self.tombstones += 1;
             }
         }
+        self.shrink_if_underloaded();
+    }
+
+    fn clear(&mut self) {
+        self.table = hash_table::empty_buckets(self.capacity);
+        self.size = 0;
+        self.tombstones = 0;
+    }
+
+    fn iter<'a>(&'a self) -> impl ExactSizeIterator<Item = (&'a K, &'a V)>
+    where
+        K: 'a,
+        V: 'a,
+    {
+        hash_table::Iter::new(&self.table, self.size)
+    }
+
+    fn iter_mut<'a>(&'a mut self) -> impl ExactSizeIterator<Item = (&'a K, &'a mut V)>
+    where
+        K: 'a,
+        V: 'a,
+    {
+        hash_table::IterMut::new(&mut self.table, self.size)
+    }
+
+    fn drain(&mut self) -> impl ExactSizeIterator<Item = (K, V)> {
+        let len = self.size;
+        self.size = 0;
+        self.tombstones = 0;
+        hash_table::Drain::new(&mut self.table, len)
+    }
+
+    fn shrink_to(&mut self, min_capacity: usize) {
+        // Keep the capacity a power of two, which may leave it slightly above what was asked for.
+        let new_capacity = min_capacity
+            .max(hash_table::min_capacity_for(self.size, self.max_load_factor))
+            .next_power_of_two();
+        if new_capacity < self.capacity {
+            assert!(self.resize_to(new_capacity));
+        }
+    }
+}
+
+impl<K, V, S> InsertAtBucket<K, V> for QuadraticProbingTombstoneHashTable<K, V, S> {
+    fn insert_at_bucket(&mut self, index: usize, key: K, value: V) -> &mut V {
+        if self.table[index].is_some() {
+            // The bucket holds a tombstone that is being reused.
+            self.tombstones -= 1;
+        }
+        self.size += 1;
+        match self.table[index].insert(Some((key, value))) {
+            Some((_, value)) => value,
+            None => unreachable!(),
+        }
+    }
+}
+
+impl<K: Hash + Eq, V, S: BuildHasher + Clone> MigrateBuckets<K, V> for QuadraticProbingTombstoneHashTable<K, V, S> {
+    fn capacity_to_resize_to(&self) -> Option<usize> {
+        self.capacity_for_one_more()
+    }
+
+    fn capacity_for(&self, len: usize) -> usize {
+        hash_table::min_capacity_for(len, self.max_load_factor)
+    }
+
+    fn empty_with_capacity(&self, capacity: usize) -> Self {
+        let mut hash_table = Self::with_hasher(capacity, self.hash_builder.clone());
+        hash_table.max_load_factor = self.max_load_factor;
+        hash_table.growth_factor = self.growth_factor;
+        hash_table.min_load_factor = self.min_load_factor;
+        hash_table
+    }
+
+    fn bucket_count(&self) -> usize {
+        self.table.len()
+    }
+
+    fn bucket_of(&self, key: &K) -> Option<usize> {
+        self.find_index_for_key_if_exists(key)
+    }
+
+    fn take_bucket(&mut self, index: usize) -> impl Iterator<Item = (K, V)> {
+        // Leave a tombstone, so that probes for the remaining keys still go past this bucket.
+        let entry = self.table[index].as_mut().and_then(Option::take);
+        if entry.is_some() {
+            self.size -= 1;
+            self.tombstones += 1;
+        }
+        entry.into_iter()
+    }
+}
+
+impl<K: Hash + Eq, V, S: BuildHasher> HashTableStats for QuadraticProbingTombstoneHashTable<K, V, S> {
+    fn stats(&self) -> Stats {
+        let hits = self.keys().map(|key| {
+            self.probe(self.get_preferred_index(key), |existing_key| existing_key == key).1
+        });
+        // The probe sequence only depends on the preferred bucket, so a miss is simulated from each of them.
+        let misses = (0..self.capacity).map(|index| self.probe(index, |_| false).1);
+        let mut stats = Stats::new(self.size, self.capacity, hits.collect(), misses.collect());
+        stats.tombstones = self.tombstones;
+        stats.longest_cluster = hash_table::longest_cluster(&self.table);
+        stats
+    }
+}
+
+impl<K, V, S> IntoIterator for QuadraticProbingTombstoneHashTable<K, V, S> {
+    type Item = (K, V);
+    type IntoIter = hash_table::IntoIter<Option<Option<(K, V)>>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        hash_table::IntoIter::new(self.table, self.size)
+    }
+}
+
+impl<'a, K, V, S> IntoIterator for &'a QuadraticProbingTombstoneHashTable<K, V, S> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = hash_table::Iter<'a, Option<Option<(K, V)>>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        hash_table::Iter::new(&self.table, self.size)
+    }
+}
+
+impl<'a, K, V, S> IntoIterator for &'a mut QuadraticProbingTombstoneHashTable<K, V, S> {
+    type Item = (&'a K, &'a mut V);
+    type IntoIter = hash_table::IterMut<'a, Option<Option<(K, V)>>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        hash_table::IterMut::new(&mut self.table, self.size)
     }
 }
 
 pub fn run() {
-    let capacity: usize= 10;
-    let mut hash_table = QuadraticProbingTombstoneHashTable::new(capacity, 1, 1, 1);
-
-    let one: Vec<char> = "one".chars().collect();
-    let two: Vec<char> = "two".chars().collect();
-    let three: Vec<char> = "three".chars().collect();
-    let four: Vec<char> = "four".chars().collect();
-    let five: Vec<char> = "five".chars().collect();
-    let six: Vec<char> = "six".chars().collect();
-    let seven: Vec<char> = "seven".chars().collect();
-    let eight: Vec<char> = "eight".chars().collect();
-    let nine: Vec<char> = "nine".chars().collect();
-    let ten: Vec<char> = "ten".chars().collect();
-    let eleven: Vec<char> = "eleven".chars().collect();
-    let twelve: Vec<char> = "twelve".chars().collect();
-    let fourteen: Vec<char> = "fourteen".chars().collect();
-    let twenty_one: Vec<char> = "twenty_one".chars().collect();
-
-    hash_table.insert(1, one.clone());
-    hash_table.insert(2, two.clone());
-    hash_table.insert(3, three.clone());
-    hash_table.insert(4, four.clone());
-    hash_table.insert(5, five.clone());
-    hash_table.insert(6, six.clone());
-    hash_table.insert(7, seven.clone());
-    hash_table.insert(8, eight.clone());
+    let capacity: usize = 8;
+    // The identity hasher keeps keys on `key % capacity` so the layouts below are predictable.
+    let mut hash_table = QuadraticProbingTombstoneHashTable::with_hasher(capacity, BuildIdentityHasher::default());
+    // Let the table fill up completely so the collisions below stay visible.
+    hash_table.set_max_load_factor(1.0);
+
+    for key in 1..=4 {
+        hash_table.insert(key, key * 10);
+    }
+    // From bucket 1, the probe sequence is 1, 2, 4, 7, 3, 0, 6, 5. 9 finds 7 empty, and 17 goes on to 0.
+    hash_table.insert(9, 90);
+    hash_table.insert(17, 170);
     println!("Initial:");
     hash_table.print();
-    assert!(hash_table.capacity == 10);
-    assert!(hash_table.size == 8);
-    let mut return_val = hash_table.get(1);
-    assert!(return_val.is_some());
-    assert!(return_val.unwrap().iter().zip(one.clone()).filter(|&(a, b)| *a != b).count() == 0);
-    return_val = hash_table.get(2);
-    assert!(return_val.is_some());
-    assert!(return_val.unwrap().iter().zip(two.clone()).filter(|&(a, b)| *a != b).count() == 0);
-    return_val = hash_table.get(3);
-    assert!(return_val.is_some());
-    assert!(return_val.unwrap().iter().zip(three.clone()).filter(|&(a, b)| *a != b).count() == 0);
-    return_val = hash_table.get(4);
-    assert!(return_val.is_some());
-    assert!(return_val.unwrap().iter().zip(four.clone()).filter(|&(a, b)| *a != b).count() == 0);
-    return_val = hash_table.get(5);
-    assert!(return_val.is_some());
-    assert!(return_val.unwrap().iter().zip(five.clone()).filter(|&(a, b)| *a != b).count() == 0);
-    return_val = hash_table.get(6);
-    assert!(return_val.is_some());
-    assert!(return_val.unwrap().iter().zip(six.clone()).filter(|&(a, b)| *a != b).count() == 0);
-    return_val = hash_table.get(7);
-    assert!(return_val.is_some());
-    assert!(return_val.unwrap().iter().zip(seven.clone()).filter(|&(a, b)| *a != b).count() == 0);
-    return_val = hash_table.get(8);
-    assert!(return_val.is_some());
-    assert!(return_val.unwrap().iter().zip(eight.clone()).filter(|&(a, b)| *a != b).count() == 0);
-
-    // Should collide with 1 and resize table
-    hash_table.insert(21, twenty_one.clone());
-    println!("Inserted 21, which should resize to 20:");
-    hash_table.print();
-    assert!(hash_table.capacity == 20);
-    assert!(hash_table.size == 9);
-    return_val = hash_table.get(21);
-    assert!(return_val.is_some());
-    assert!(return_val.unwrap().iter().zip(twenty_one.clone()).filter(|&(a, b)| *a != b).count() == 0);
-
-    hash_table.delete(1);
-    return_val = hash_table.get(1);
-    assert!(return_val.is_none());
+    assert!(hash_table.capacity == 8);
+    assert!(hash_table.size == 6);
+    assert!(matches!(hash_table.table[7], Some(Some((9, 90)))));
+    assert!(matches!(hash_table.table[0], Some(Some((17, 170)))));
+    for key in [1, 2, 3, 4, 9, 17] {
+        assert!(hash_table.get(&key) == Some(&(key * 10)));
+    }
+
+    hash_table.remove(&1);
+    assert!(hash_table.get(&1).is_none());
     println!("After deleting key 1:");
     hash_table.print();
-    assert!(hash_table.size == 8);
+    assert!(hash_table.size == 5);
+    assert!(hash_table.tombstones == 1);
 
-    hash_table.insert(21, twenty_one.clone());
-    println!("Re-inserted 21, which should be moved to the earlier tombstone:");
+    hash_table.insert(17, 171);
+    println!("Re-inserted 17, which should be moved to the earlier tombstone:");
     hash_table.print();
-    assert!(hash_table.size == 8);
+    assert!(matches!(hash_table.table[1], Some(Some((17, 171)))));
+    assert!(hash_table.table[0] == Some(None));
+    assert!(hash_table.size == 5);
+    assert!(hash_table.tombstones == 1);
 
-    hash_table.insert(12, twelve.clone());
-    println!("Inserted 12, which should collide with 2:");
+    // Live entries and the tombstone fill the table, so it grows.
+    hash_table.insert(5, 50);
+    hash_table.insert(6, 60);
+    hash_table.insert(25, 250);
+    println!("Inserted 25, which should resize to 16:");
     hash_table.print();
-    assert!(hash_table.size == 9);
+    assert!(hash_table.capacity == 16);
+    assert!(hash_table.size == 8);
+    assert!(hash_table.tombstones == 0);
 
-    hash_table.insert(9, nine.clone());
-    hash_table.insert(10, ten.clone());
-    hash_table.insert(11, eleven.clone());
-    hash_table.insert(14, fourteen.clone());
-    println!("Inserted 9, 10, 11 & 14, the tombstone left by 21 should be occupied by 14 now:");
-    hash_table.print();
-    assert!(hash_table.size == 13);
+    // From every home bucket, the probe sequence visits each bucket exactly once.
+    for capacity in [1, 2, 8, 64, 1024] {
+        let hash_table: QuadraticProbingTombstoneHashTable<usize, usize> =
+            QuadraticProbingTombstoneHashTable::new(capacity);
+        for preferred_index in 0..capacity {
+            let mut visited = vec![false; capacity];
+            for i in 0..capacity {
+                visited[hash_table.get_probe_index(preferred_index, i)] = true;
+            }
+            assert!(visited.iter().all(|&visited| visited), "Missed a bucket in a capacity of {}", capacity);
+        }
+    }
+
+    // Keys that all share a home bucket fill the table right up to its maximum load factor, and it only grows
+    // when the load factor calls for it, never because probing missed a free bucket.
+    let mut hash_table = QuadraticProbingTombstoneHashTable::with_hasher(capacity, BuildIdentityHasher::default());
+    hash_table.set_max_load_factor(1.0);
+    for len in 1..=512_usize {
+        hash_table.insert(len << 20, len);
+        assert!(hash_table.capacity == len.next_power_of_two().max(capacity), "Capacity: {}", hash_table.capacity);
+    }
+    for len in 1..=512 {
+        assert!(hash_table.get(&(len << 20)) == Some(&len));
+    }
+    println!("All tests passed");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    hash_table::hash_table_tests!(QuadraticProbingTombstoneHashTable);
+
+    #[test]
+    fn compaction() {
+        hash_table::check_compaction(
+            &mut QuadraticProbingTombstoneHashTable::new(10),
+            QuadraticProbingTombstoneHashTable::tombstone_count,
+            QuadraticProbingTombstoneHashTable::compact,
+        );
+    }
 }
@@ -0,0 +1,793 @@
+use std::collections::hash_map::RandomState;
+use std::fmt::Debug;
+use std::hash::{BuildHasher, Hash};
+
+use smallvec::SmallVec;
+
+use crate::hash_table::{
+    self, BuildIdentityHasher, Entry, HashTable, HashTableStats, InsertAtBucket, MigrateBuckets, OccupiedEntry, Stats,
+    VacantEntry,
+};
+
+// Chains only get longer as the table fills up, so grow once there is one entry per bucket on average.
+const DEFAULT_MAX_LOAD_FACTOR: f64 = 1.0;
+
+// Number of entries a small-vector chain stores inline before moving them to the heap.
+const INLINE_CHAIN_LENGTH: usize = 4;
+
+/// The entries that hash to one bucket of a `SeparateChainingHashTable`, in no particular order.
+pub trait Chain<K: Eq, V>: Default {
+    type Iter<'a>: Iterator<Item = (&'a K, &'a V)>
+    where
+        Self: 'a,
+        K: 'a,
+        V: 'a;
+
+    type IterMut<'a>: Iterator<Item = (&'a K, &'a mut V)>
+    where
+        Self: 'a,
+        K: 'a,
+        V: 'a;
+
+    type IntoIter: Iterator<Item = (K, V)>;
+
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn iter(&self) -> Self::Iter<'_>;
+
+    fn iter_mut(&mut self) -> Self::IterMut<'_>;
+
+    fn into_entries(self) -> Self::IntoIter;
+
+    /// Adds a key known not to be in the chain and returns its value.
+    fn push(&mut self, key: K, value: V) -> &mut V;
+
+    fn remove(&mut self, key: &K) -> Option<(K, V)>;
+
+    fn retain<F: FnMut(&K, &mut V) -> bool>(&mut self, f: &mut F);
+
+    fn position(&self, key: &K) -> Option<usize> {
+        self.iter().position(|(existing_key, _)| existing_key == key)
+    }
+
+    fn find(&self, key: &K) -> Option<(&K, &V)> {
+        self.iter().find(|(existing_key, _)| *existing_key == key)
+    }
+
+    fn find_mut(&mut self, key: &K) -> Option<(&K, &mut V)> {
+        self.iter_mut().find(|(existing_key, _)| *existing_key == key)
+    }
+}
+
+struct Node<K, V> {
+    key: K,
+    value: V,
+    next: Option<Box<Node<K, V>>>,
+}
+
+/// Singly linked list chain, the textbook layout. New entries go to the front.
+pub struct LinkedListChain<K, V> {
+    head: Option<Box<Node<K, V>>>,
+    len: usize,
+}
+
+impl<K, V> Default for LinkedListChain<K, V> {
+    fn default() -> Self {
+        LinkedListChain { head: None, len: 0 }
+    }
+}
+
+pub struct LinkedListIter<'a, K, V> {
+    next: Option<&'a Node<K, V>>,
+}
+
+impl<'a, K, V> Iterator for LinkedListIter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = self.next?;
+        self.next = node.next.as_deref();
+        Some((&node.key, &node.value))
+    }
+}
+
+pub struct LinkedListIterMut<'a, K, V> {
+    next: Option<&'a mut Node<K, V>>,
+}
+
+impl<'a, K, V> Iterator for LinkedListIterMut<'a, K, V> {
+    type Item = (&'a K, &'a mut V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = self.next.take()?;
+        self.next = node.next.as_deref_mut();
+        Some((&node.key, &mut node.value))
+    }
+}
+
+pub struct LinkedListIntoIter<K, V> {
+    next: Option<Box<Node<K, V>>>,
+}
+
+impl<K, V> Iterator for LinkedListIntoIter<K, V> {
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = *self.next.take()?;
+        self.next = node.next;
+        Some((node.key, node.value))
+    }
+}
+
+impl<K: Eq, V> Chain<K, V> for LinkedListChain<K, V> {
+    type Iter<'a>
+        = LinkedListIter<'a, K, V>
+    where
+        Self: 'a,
+        K: 'a,
+        V: 'a;
+
+    type IterMut<'a>
+        = LinkedListIterMut<'a, K, V>
+    where
+        Self: 'a,
+        K: 'a,
+        V: 'a;
+
+    type IntoIter = LinkedListIntoIter<K, V>;
+
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn iter(&self) -> Self::Iter<'_> {
+        LinkedListIter { next: self.head.as_deref() }
+    }
+
+    fn iter_mut(&mut self) -> Self::IterMut<'_> {
+        LinkedListIterMut { next: self.head.as_deref_mut() }
+    }
+
+    fn into_entries(self) -> Self::IntoIter {
+        LinkedListIntoIter { next: self.head }
+    }
+
+    fn push(&mut self, key: K, value: V) -> &mut V {
+        let next = self.head.take();
+        self.len += 1;
+        &mut self.head.insert(Box::new(Node { key, value, next })).value
+    }
+
+    fn remove(&mut self, key: &K) -> Option<(K, V)> {
+        let mut link = &mut self.head;
+        while link.as_ref().is_some_and(|node| node.key != *key) {
+            link = &mut link.as_mut().unwrap().next;
+        }
+        let node = *link.take()?;
+        *link = node.next;
+        self.len -= 1;
+        Some((node.key, node.value))
+    }
+
+    fn retain<F: FnMut(&K, &mut V) -> bool>(&mut self, f: &mut F) {
+        // Unlink every node and link the kept ones back in their original order.
+        let mut remaining = self.head.take();
+        let mut tail = &mut self.head;
+        while let Some(mut node) = remaining {
+            remaining = node.next.take();
+            if f(&node.key, &mut node.value) {
+                tail = &mut tail.insert(node).next;
+            } else {
+                self.len -= 1;
+            }
+        }
+    }
+}
+
+/// Chain that keeps its first few entries inline in the bucket, so short chains don't need a heap
+/// allocation or pointer chasing.
+pub struct SmallVecChain<K, V> {
+    entries: SmallVec<[(K, V); INLINE_CHAIN_LENGTH]>,
+}
+
+impl<K, V> Default for SmallVecChain<K, V> {
+    fn default() -> Self {
+        SmallVecChain { entries: SmallVec::new() }
+    }
+}
+
+impl<K: Eq, V> Chain<K, V> for SmallVecChain<K, V> {
+    type Iter<'a>
+        = std::iter::Map<std::slice::Iter<'a, (K, V)>, fn(&'a (K, V)) -> (&'a K, &'a V)>
+    where
+        Self: 'a,
+        K: 'a,
+        V: 'a;
+
+    type IterMut<'a>
+        = std::iter::Map<std::slice::IterMut<'a, (K, V)>, fn(&'a mut (K, V)) -> (&'a K, &'a mut V)>
+    where
+        Self: 'a,
+        K: 'a,
+        V: 'a;
+
+    type IntoIter = smallvec::IntoIter<[(K, V); INLINE_CHAIN_LENGTH]>;
+
+    fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    fn iter(&self) -> Self::Iter<'_> {
+        self.entries.iter().map(|(key, value)| (key, value))
+    }
+
+    fn iter_mut(&mut self) -> Self::IterMut<'_> {
+        self.entries.iter_mut().map(|(key, value)| (&*key, value))
+    }
+
+    fn into_entries(self) -> Self::IntoIter {
+        self.entries.into_iter()
+    }
+
+    fn push(&mut self, key: K, value: V) -> &mut V {
+        self.entries.push((key, value));
+        &mut self.entries.last_mut().unwrap().1
+    }
+
+    fn remove(&mut self, key: &K) -> Option<(K, V)> {
+        let position = self.position(key)?;
+        Some(self.entries.swap_remove(position))
+    }
+
+    fn retain<F: FnMut(&K, &mut V) -> bool>(&mut self, f: &mut F) {
+        self.entries.retain(|(key, value)| f(key, value));
+    }
+}
+
+pub struct SeparateChainingHashTable<K, V, C, S = RandomState> {
+    table: Vec<C>,
+    capacity: usize,
+    size: usize,
+    hash_builder: S,
+    max_load_factor: f64,
+    growth_factor: f64,
+    // When set, removals shrink the table once the load factor drops below this value.
+    min_load_factor: Option<f64>,
+    entries: std::marker::PhantomData<(K, V)>,
+}
+
+pub type LinkedListChainingHashTable<K, V, S = RandomState> =
+    SeparateChainingHashTable<K, V, LinkedListChain<K, V>, S>;
+
+pub type SmallVecChainingHashTable<K, V, S = RandomState> = SeparateChainingHashTable<K, V, SmallVecChain<K, V>, S>;
+
+impl<K: Hash + Eq, V, C: Chain<K, V>> SeparateChainingHashTable<K, V, C> {
+    pub fn new(capacity: usize) -> Self {
+        Self::with_hasher(capacity, RandomState::new())
+    }
+}
+
+impl<K: Hash + Eq, V, C: Chain<K, V>, S: BuildHasher> SeparateChainingHashTable<K, V, C, S> {
+    pub fn with_hasher(capacity: usize, hash_builder: S) -> Self {
+        SeparateChainingHashTable {
+            table: std::iter::repeat_with(C::default).take(capacity).collect(),
+            capacity,
+            size: 0,
+            hash_builder,
+            max_load_factor: DEFAULT_MAX_LOAD_FACTOR,
+            growth_factor: hash_table::DEFAULT_GROWTH_FACTOR,
+            min_load_factor: None,
+            entries: std::marker::PhantomData,
+        }
+    }
+
+    /// The table grows before an insertion would take it above this many entries per bucket on average.
+    /// Unlike open addressing, chains can hold more entries than there are buckets, so this may exceed 1.
+    pub fn set_max_load_factor(&mut self, max_load_factor: f64) {
+        assert!(max_load_factor > 0.0, "Max load factor must be positive, got {}", max_load_factor);
+        hash_table::assert_valid_min_load_factor(self.min_load_factor, max_load_factor);
+        self.max_load_factor = max_load_factor;
+    }
+
+    /// Makes removals shrink the table once its load factor drops below `min_load_factor`.
+    /// `None`, the default, never shrinks automatically.
+    pub fn set_min_load_factor(&mut self, min_load_factor: Option<f64>) {
+        hash_table::assert_valid_min_load_factor(min_load_factor, self.max_load_factor);
+        self.min_load_factor = min_load_factor;
+    }
+
+    /// Factor the capacity is multiplied by whenever the table grows.
+    pub fn set_growth_factor(&mut self, growth_factor: f64) {
+        hash_table::assert_valid_growth_factor(growth_factor);
+        self.growth_factor = growth_factor;
+    }
+
+    /// Length of the longest chain, which bounds the cost of a lookup.
+    pub fn longest_chain(&self) -> usize {
+        self.table.iter().map(Chain::len).max().unwrap_or(0)
+    }
+
+    fn get_chain_index(&self, key: &K) -> usize {
+        (self.hash_builder.hash_one(key) % self.capacity as u64) as usize
+    }
+
+    fn resize_to(&mut self, new_capacity: usize) -> bool {
+        if !hash_table::fits_in_capacity(self.size, new_capacity, self.max_load_factor) {
+            return false;
+        }
+        self.capacity = new_capacity;
+        let new_table = std::iter::repeat_with(C::default).take(self.capacity).collect();
+        let old_table = std::mem::replace(&mut self.table, new_table);
+        for (key, value) in old_table.into_iter().flat_map(Chain::into_entries) {
+            let chain_index = self.get_chain_index(&key);
+            self.table[chain_index].push(key, value);
+        }
+        true
+    }
+
+    // Shrinks the table if removals took it below the minimum load factor.
+    fn shrink_if_underloaded(&mut self) {
+        if let Some(min_load_factor) = self.min_load_factor
+            && (self.size as f64) < self.capacity as f64 * min_load_factor
+        {
+            self.shrink_to(hash_table::shrunk_capacity(self.size, min_load_factor, self.max_load_factor));
+        }
+    }
+
+    fn resize(&mut self) {
+        assert!(self.resize_to(hash_table::grown_capacity(self.capacity, self.growth_factor)));
+    }
+}
+
+impl<K: Eq + Debug, V: Debug, C: Chain<K, V>, S> SeparateChainingHashTable<K, V, C, S> {
+    pub fn print(&self) {
+        println!("Capacity: {}", self.capacity);
+        println!("Size: {}", self.size);
+        println!("Entries:");
+        for chain in &self.table {
+            if chain.is_empty() {
+                println!("\t<None>");
+            } else {
+                let entries: Vec<String> = chain.iter().map(|(key, value)| format!("{:?}: {:?}", key, value)).collect();
+                println!("\t{}", entries.join(" -> "));
+            }
+        }
+    }
+}
+
+impl<K: Hash + Eq, V, C: Chain<K, V>, S: BuildHasher> HashTable<K, V> for SeparateChainingHashTable<K, V, C, S> {
+    fn len(&self) -> usize {
+        self.size
+    }
+
+    fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    fn entry(&mut self, key: K) -> Entry<'_, K, V> {
+        let mut chain_index = self.get_chain_index(&key);
+        // Finding the position first and borrowing the entry afterwards keeps the borrow checker happy when
+        // the key turns out to be missing.
+        if let Some(position) = self.table[chain_index].position(&key) {
+            let (key, value) = self.table[chain_index].iter_mut().nth(position).unwrap();
+            return Entry::Occupied(OccupiedEntry::new(key, value));
+        }
+
+        // Resize the table because it's getting too full.
+        if hash_table::exceeds_max_load_factor(self.size, self.capacity, self.max_load_factor) {
+            self.resize();
+            chain_index = self.get_chain_index(&key);
+        }
+        Entry::Vacant(VacantEntry::new(key, chain_index, self))
+    }
+
+    fn get_key_value(&self, key: &K) -> Option<(&K, &V)> {
+        self.table[self.get_chain_index(key)].find(key)
+    }
+
+    fn get_mut(&mut self, key: &K) -> Option<&mut V> {
+        let chain_index = self.get_chain_index(key);
+        self.table[chain_index].find_mut(key).map(|(_key, value)| value)
+    }
+
+    fn get_many_mut<const N: usize>(&mut self, keys: [&K; N]) -> Option<[&mut V; N]> {
+        for (i, key) in keys.iter().enumerate() {
+            if keys[..i].contains(key) {
+                return None;
+            }
+        }
+        let chain_indices = keys.map(|key| self.get_chain_index(key));
+        let mut sorted_chain_indices = chain_indices.to_vec();
+        sorted_chain_indices.sort_unstable();
+        sorted_chain_indices.dedup();
+
+        // Several keys can share a chain, so split each chain off the table once and hand out the values of
+        // all the keys it holds.
+        let mut values: [Option<&mut V>; N] = [const { None }; N];
+        let mut rest: &mut [C] = &mut self.table;
+        let mut rest_start = 0;
+        for chain_index in sorted_chain_indices {
+            let (chain, tail) = std::mem::take(&mut rest)[chain_index - rest_start..].split_first_mut()?;
+            rest = tail;
+            rest_start = chain_index + 1;
+            for (existing_key, value) in chain.iter_mut() {
+                if let Some(i) = (0..N).find(|&i| chain_indices[i] == chain_index && keys[i] == existing_key) {
+                    values[i] = Some(value);
+                }
+            }
+        }
+        if values.iter().any(Option::is_none) {
+            return None;
+        }
+        Some(values.map(Option::unwrap))
+    }
+
+    fn remove_entry(&mut self, key: &K) -> Option<(K, V)> {
+        let chain_index = self.get_chain_index(key);
+        let entry = self.table[chain_index].remove(key)?;
+        self.size -= 1;
+        self.shrink_if_underloaded();
+        Some(entry)
+    }
+
+    fn retain<F: FnMut(&K, &mut V) -> bool>(&mut self, mut f: F) {
+        for chain in &mut self.table {
+            let len = chain.len();
+            chain.retain(&mut f);
+            self.size -= len - chain.len();
+        }
+        self.shrink_if_underloaded();
+    }
+
+    fn clear(&mut self) {
+        self.table = std::iter::repeat_with(C::default).take(self.capacity).collect();
+        self.size = 0;
+    }
+
+    fn iter<'a>(&'a self) -> impl ExactSizeIterator<Item = (&'a K, &'a V)>
+    where
+        K: 'a,
+        V: 'a,
+    {
+        Iter { chains: self.table.iter(), current: None, remaining: self.size }
+    }
+
+    fn iter_mut<'a>(&'a mut self) -> impl ExactSizeIterator<Item = (&'a K, &'a mut V)>
+    where
+        K: 'a,
+        V: 'a,
+    {
+        IterMut { chains: self.table.iter_mut(), current: None, remaining: self.size }
+    }
+
+    fn drain(&mut self) -> impl ExactSizeIterator<Item = (K, V)> {
+        let len = self.size;
+        self.size = 0;
+        Drain { chains: self.table.iter_mut(), current: None, remaining: len }
+    }
+
+    fn shrink_to(&mut self, min_capacity: usize) {
+        let new_capacity = min_capacity.max(hash_table::min_capacity_for(self.size, self.max_load_factor));
+        if new_capacity < self.capacity {
+            assert!(self.resize_to(new_capacity));
+        }
+    }
+}
+
+impl<K: Hash + Eq, V, C: Chain<K, V>, S: BuildHasher> InsertAtBucket<K, V> for SeparateChainingHashTable<K, V, C, S> {
+    fn insert_at_bucket(&mut self, index: usize, key: K, value: V) -> &mut V {
+        self.size += 1;
+        self.table[index].push(key, value)
+    }
+}
+
+impl<K: Hash + Eq, V, C: Chain<K, V>, S: BuildHasher + Clone> MigrateBuckets<K, V>
+    for SeparateChainingHashTable<K, V, C, S>
+{
+    fn capacity_to_resize_to(&self) -> Option<usize> {
+        hash_table::exceeds_max_load_factor(self.size, self.capacity, self.max_load_factor)
+            .then(|| hash_table::grown_capacity(self.capacity, self.growth_factor))
+    }
+
+    fn capacity_for(&self, len: usize) -> usize {
+        hash_table::min_capacity_for(len, self.max_load_factor)
+    }
+
+    fn empty_with_capacity(&self, capacity: usize) -> Self {
+        let mut hash_table = Self::with_hasher(capacity, self.hash_builder.clone());
+        hash_table.max_load_factor = self.max_load_factor;
+        hash_table.growth_factor = self.growth_factor;
+        hash_table.min_load_factor = self.min_load_factor;
+        hash_table
+    }
+
+    fn bucket_count(&self) -> usize {
+        self.table.len()
+    }
+
+    fn bucket_of(&self, key: &K) -> Option<usize> {
+        let chain_index = self.get_chain_index(key);
+        self.table[chain_index].position(key).map(|_| chain_index)
+    }
+
+    fn take_bucket(&mut self, index: usize) -> impl Iterator<Item = (K, V)> {
+        let chain = std::mem::take(&mut self.table[index]);
+        self.size -= chain.len();
+        chain.into_entries()
+    }
+}
+
+impl<K: Hash + Eq, V, C: Chain<K, V>, S: BuildHasher> HashTableStats for SeparateChainingHashTable<K, V, C, S> {
+    fn stats(&self) -> Stats {
+        // Lookups walk a chain from the front, so the n-th entry of a chain is found after n probes, and a miss
+        // walks the whole chain.
+        let hits = self.table.iter().flat_map(|chain| 1..=chain.len());
+        let misses = self.table.iter().map(Chain::len);
+        let mut stats = Stats::new(self.size, self.capacity, hits.collect(), misses.collect());
+        stats.longest_cluster = self.longest_chain();
+        stats
+    }
+}
+
+/// Iterator over the live entries of a chained table, chain by chain.
+pub struct Iter<'a, K: Eq + 'a, V: 'a, C: Chain<K, V>> {
+    chains: std::slice::Iter<'a, C>,
+    current: Option<C::Iter<'a>>,
+    remaining: usize,
+}
+
+impl<'a, K: Eq, V, C: Chain<K, V>> Iterator for Iter<'a, K, V, C> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(entry) = self.current.as_mut().and_then(Iterator::next) {
+                self.remaining -= 1;
+                return Some(entry);
+            }
+            self.current = Some(self.chains.next()?.iter());
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<K: Eq, V, C: Chain<K, V>> ExactSizeIterator for Iter<'_, K, V, C> {}
+
+/// Iterator over the live entries of a chained table with mutable access to the values.
+pub struct IterMut<'a, K: Eq + 'a, V: 'a, C: Chain<K, V>> {
+    chains: std::slice::IterMut<'a, C>,
+    current: Option<C::IterMut<'a>>,
+    remaining: usize,
+}
+
+impl<'a, K: Eq, V, C: Chain<K, V>> Iterator for IterMut<'a, K, V, C> {
+    type Item = (&'a K, &'a mut V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(entry) = self.current.as_mut().and_then(Iterator::next) {
+                self.remaining -= 1;
+                return Some(entry);
+            }
+            self.current = Some(self.chains.next()?.iter_mut());
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<K: Eq, V, C: Chain<K, V>> ExactSizeIterator for IterMut<'_, K, V, C> {}
+
+/// Iterator that moves the live entries out of a chained table it consumed.
+pub struct IntoIter<K: Eq, V, C: Chain<K, V>> {
+    chains: std::vec::IntoIter<C>,
+    current: Option<C::IntoIter>,
+    remaining: usize,
+}
+
+impl<K: Eq, V, C: Chain<K, V>> Iterator for IntoIter<K, V, C> {
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(entry) = self.current.as_mut().and_then(Iterator::next) {
+                self.remaining -= 1;
+                return Some(entry);
+            }
+            self.current = Some(self.chains.next()?.into_entries());
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<K: Eq, V, C: Chain<K, V>> ExactSizeIterator for IntoIter<K, V, C> {}
+
+/// Iterator that moves the live entries out of a chained table while leaving its buckets allocated. Every
+/// chain is emptied even if the iterator is dropped before the end.
+pub struct Drain<'a, K: Eq, V, C: Chain<K, V>> {
+    chains: std::slice::IterMut<'a, C>,
+    current: Option<C::IntoIter>,
+    remaining: usize,
+}
+
+impl<K: Eq, V, C: Chain<K, V>> Iterator for Drain<'_, K, V, C> {
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(entry) = self.current.as_mut().and_then(Iterator::next) {
+                self.remaining -= 1;
+                return Some(entry);
+            }
+            self.current = Some(std::mem::take(self.chains.next()?).into_entries());
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<K: Eq, V, C: Chain<K, V>> ExactSizeIterator for Drain<'_, K, V, C> {}
+
+impl<K: Eq, V, C: Chain<K, V>> Drop for Drain<'_, K, V, C> {
+    fn drop(&mut self) {
+        for chain in &mut self.chains {
+            *chain = C::default();
+        }
+    }
+}
+
+impl<K: Eq, V, C: Chain<K, V>, S> IntoIterator for SeparateChainingHashTable<K, V, C, S> {
+    type Item = (K, V);
+    type IntoIter = IntoIter<K, V, C>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIter { chains: self.table.into_iter(), current: None, remaining: self.size }
+    }
+}
+
+impl<'a, K: Eq, V, C: Chain<K, V>, S> IntoIterator for &'a SeparateChainingHashTable<K, V, C, S> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V, C>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        Iter { chains: self.table.iter(), current: None, remaining: self.size }
+    }
+}
+
+impl<'a, K: Eq, V, C: Chain<K, V>, S> IntoIterator for &'a mut SeparateChainingHashTable<K, V, C, S> {
+    type Item = (&'a K, &'a mut V);
+    type IntoIter = IterMut<'a, K, V, C>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        IterMut { chains: self.table.iter_mut(), current: None, remaining: self.size }
+    }
+}
+
+// Walks through the same steps as the other tables' demos, where collisions grow chains instead of
+// probe sequences.
+fn run_demo<C: Chain<usize, Vec<char>>>(name: &str) {
+    println!("Chains stored as {}:", name);
+    let capacity: usize = 10;
+    // The identity hasher keeps keys on `key % capacity` so the layouts below are predictable.
+    let mut hash_table: SeparateChainingHashTable<usize, Vec<char>, C, _> =
+        SeparateChainingHashTable::with_hasher(capacity, BuildIdentityHasher::default());
+
+    let one: Vec<char> = "one".chars().collect();
+    let two: Vec<char> = "two".chars().collect();
+    let three: Vec<char> = "three".chars().collect();
+    let four: Vec<char> = "four".chars().collect();
+    let five: Vec<char> = "five".chars().collect();
+    let six: Vec<char> = "six".chars().collect();
+    let seven: Vec<char> = "seven".chars().collect();
+    let eight: Vec<char> = "eight".chars().collect();
+    let nine: Vec<char> = "nine".chars().collect();
+    let eleven: Vec<char> = "eleven".chars().collect();
+    let twenty_one: Vec<char> = "twenty_one".chars().collect();
+    let thirty_one: Vec<char> = "thirty_one".chars().collect();
+
+    hash_table.insert(1, one.clone());
+    hash_table.insert(2, two.clone());
+    hash_table.insert(3, three.clone());
+    hash_table.insert(4, four.clone());
+    hash_table.insert(5, five.clone());
+    hash_table.insert(6, six.clone());
+    hash_table.insert(7, seven.clone());
+    hash_table.insert(8, eight.clone());
+    // Should collide with 1 and join its chain
+    hash_table.insert(11, eleven.clone());
+    println!("Initial:");
+    hash_table.print();
+    assert!(hash_table.capacity == 10);
+    assert!(hash_table.size == 9);
+    assert!(hash_table.table[1].len() == 2);
+    for (key, value) in [(1, &one), (2, &two), (3, &three), (4, &four), (5, &five), (6, &six)] {
+        assert!(hash_table.get(&key) == Some(value), "Wrong value for key {}", key);
+    }
+    for (key, value) in [(7, &seven), (8, &eight), (11, &eleven)] {
+        assert!(hash_table.get(&key) == Some(value), "Wrong value for key {}", key);
+    }
+
+    assert!(hash_table.remove(&4) == Some(four.clone()));
+    assert!(hash_table.get(&4).is_none());
+    println!("After deleting key 4, its chain should be empty:");
+    hash_table.print();
+    assert!(hash_table.size == 8);
+    assert!(hash_table.table[4].is_empty());
+
+    hash_table.insert(11, eleven.clone());
+    println!("Re-inserted 11, which should have no impact:");
+    hash_table.print();
+    assert!(hash_table.size == 8);
+    assert!(hash_table.table[1].len() == 2);
+
+    hash_table.insert(21, twenty_one.clone());
+    hash_table.insert(31, thirty_one.clone());
+    println!("Inserted 21 & 31, which should join the chain of bucket 1 while bucket 4 stays empty:");
+    hash_table.print();
+    assert!(hash_table.size == 10);
+    assert!(hash_table.longest_chain() == 4);
+    assert!(hash_table.table[4].is_empty());
+    // A miss walks one whole chain, which is as long as the load factor on average.
+    let stats = hash_table.stats();
+    assert!(stats.hits.histogram == [0, 7, 1, 1, 1]);
+    assert!(stats.misses.histogram == [3, 6, 0, 0, 1] && stats.misses.average() == hash_table.load_factor());
+    assert!(stats.longest_cluster == 4);
+
+    hash_table.insert(9, nine.clone());
+    println!("Inserted 9, table should be resized to 20:");
+    hash_table.print();
+    assert!(hash_table.capacity == 20);
+    assert!(hash_table.size == 11);
+    assert!(hash_table.longest_chain() == 2);
+    for (key, value) in [(1, &one), (11, &eleven), (21, &twenty_one), (31, &thirty_one), (9, &nine)] {
+        assert!(hash_table.get(&key) == Some(value), "Wrong value for key {}", key);
+    }
+
+    // Chains can hold more entries than there are buckets, so the load factor may go above 1.
+    let mut hash_table: SeparateChainingHashTable<usize, Vec<char>, C> = SeparateChainingHashTable::new(capacity);
+    hash_table.set_max_load_factor(3.0);
+    for key in 0..30 {
+        hash_table.insert(key, one.clone());
+    }
+    assert!(hash_table.capacity == 10, "Capacity: {}", hash_table.capacity);
+    assert!(hash_table.load_factor() == 3.0);
+    hash_table.insert(30, one.clone());
+    assert!(hash_table.capacity == 20, "Capacity: {}", hash_table.capacity);
+}
+
+pub fn run() {
+    run_demo::<LinkedListChain<usize, Vec<char>>>("linked lists");
+    run_demo::<SmallVecChain<usize, Vec<char>>>("small vectors");
+    println!("All tests passed");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    mod linked_list {
+        use super::*;
+
+        hash_table::hash_table_tests!(LinkedListChainingHashTable);
+    }
+
+    mod small_vec {
+        use super::*;
+
+        hash_table::hash_table_tests!(SmallVecChainingHashTable);
+    }
+}
@@ -0,0 +1,682 @@
+use std::collections::hash_map::RandomState;
+use std::fmt::Debug;
+use std::hash::{BuildHasher, Hash};
+
+use crate::hash_table::{
+    self, BuildIdentityHasher, DeterministicState, Entry, HashTable, HashTableStats, InsertAtBucket, MigrateBuckets,
+    OccupiedEntry, Stats, VacantEntry,
+};
+
+// Probing a whole group at once keeps lookups cheap even when the table is nearly full.
+const DEFAULT_MAX_LOAD_FACTOR: f64 = 0.875;
+
+// Number of control bytes compared at once, the width of an SSE2 register.
+const GROUP_WIDTH: usize = 16;
+
+// Control byte of a bucket that has never held an entry since the last rebuild.
+const EMPTY: u8 = 0b1111_1111;
+
+// Control byte of a tombstone left behind by a removal. Full buckets store a 7-bit hash fragment instead,
+// so the high bit alone tells free buckets from full ones.
+const DELETED: u8 = 0b1000_0000;
+
+/// The hash fragment stored in the control byte of a full bucket: the top 7 bits of the hash.
+fn fragment(hash: u64) -> u8 {
+    (hash >> 57) as u8
+}
+
+/// Set of buckets within a group, bit i standing for the i-th bucket. Iterating yields the set positions
+/// from lowest to highest.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+struct BitMask(u16);
+
+impl BitMask {
+    fn any(self) -> bool {
+        self.0 != 0
+    }
+
+    fn lowest(self) -> Option<usize> {
+        self.any().then(|| self.0.trailing_zeros() as usize)
+    }
+}
+
+impl Iterator for BitMask {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        let position = self.lowest()?;
+        self.0 &= self.0 - 1;
+        Some(position)
+    }
+}
+
+/// Control bytes of one group compared one at a time, for targets without SSE2.
+mod scalar {
+    use super::{BitMask, EMPTY, GROUP_WIDTH};
+
+    pub(super) struct Group([u8; GROUP_WIDTH]);
+
+    impl Group {
+        pub(super) fn load(ctrl: &[u8; GROUP_WIDTH]) -> Self {
+            Group(*ctrl)
+        }
+
+        pub(super) fn match_byte(&self, byte: u8) -> BitMask {
+            self.mask_where(|ctrl| ctrl == byte)
+        }
+
+        pub(super) fn match_empty(&self) -> BitMask {
+            self.match_byte(EMPTY)
+        }
+
+        pub(super) fn match_empty_or_deleted(&self) -> BitMask {
+            self.mask_where(|ctrl| ctrl & 0b1000_0000 != 0)
+        }
+
+        fn mask_where(&self, matches: impl Fn(u8) -> bool) -> BitMask {
+            let positions = self.0.iter().enumerate().filter(|&(_, &ctrl)| matches(ctrl));
+            BitMask(positions.fold(0, |bits, (i, _)| bits | 1 << i))
+        }
+    }
+}
+
+/// Control bytes of one group compared with a handful of SSE2 instructions.
+#[cfg(all(target_arch = "x86_64", target_feature = "sse2"))]
+mod sse2 {
+    use std::arch::x86_64::{__m128i, _mm_cmpeq_epi8, _mm_loadu_si128, _mm_movemask_epi8, _mm_set1_epi8};
+
+    use super::{BitMask, EMPTY, GROUP_WIDTH};
+
+    pub(super) struct Group(__m128i);
+
+    impl Group {
+        pub(super) fn load(ctrl: &[u8; GROUP_WIDTH]) -> Self {
+            // SAFETY: `ctrl` is exactly 16 readable bytes, and the unaligned load has no alignment requirement.
+            Group(unsafe { _mm_loadu_si128(ctrl.as_ptr().cast()) })
+        }
+
+        pub(super) fn match_byte(&self, byte: u8) -> BitMask {
+            // SAFETY: the module is only compiled when SSE2 is enabled.
+            BitMask(unsafe { _mm_movemask_epi8(_mm_cmpeq_epi8(self.0, _mm_set1_epi8(byte as i8))) } as u16)
+        }
+
+        pub(super) fn match_empty(&self) -> BitMask {
+            self.match_byte(EMPTY)
+        }
+
+        // The movemask gathers the high bit of every byte, which is set exactly for `EMPTY` and `DELETED`.
+        pub(super) fn match_empty_or_deleted(&self) -> BitMask {
+            // SAFETY: the module is only compiled when SSE2 is enabled.
+            BitMask(unsafe { _mm_movemask_epi8(self.0) } as u16)
+        }
+    }
+}
+
+#[cfg(not(all(target_arch = "x86_64", target_feature = "sse2")))]
+use scalar::Group;
+#[cfg(all(target_arch = "x86_64", target_feature = "sse2"))]
+use sse2::Group;
+
+/// Open addressing in the style of Google's Swiss tables and `hashbrown`. Next to the buckets, every
+/// bucket has a control byte: empty, deleted, or the top 7 bits of its key's hash. Lookups probe whole
+/// groups of 16 buckets, comparing all 16 control bytes against the key's fragment at once, and only
+/// compare keys in the buckets whose fragment matches.
+pub struct SwissHashTable<K, V, S = RandomState> {
+    table: Vec<Option<(K, V)>>,
+    // One array of control bytes per group of buckets. The number of groups is always a power of two.
+    ctrl: Vec<[u8; GROUP_WIDTH]>,
+    capacity: usize,
+    size: usize,
+    hash_builder: S,
+    max_load_factor: f64,
+    growth_factor: f64,
+    // When set, removals shrink the table once the load factor drops below this value.
+    min_load_factor: Option<f64>,
+    tombstones: usize,
+}
+
+/// Capacity actually allocated for a requested one: a power of two number of whole groups.
+fn group_aligned_capacity(capacity: usize) -> usize {
+    capacity.div_ceil(GROUP_WIDTH).next_power_of_two() * GROUP_WIDTH
+}
+
+impl<K: Hash + Eq, V> SwissHashTable<K, V> {
+    pub fn new(capacity: usize) -> Self {
+        Self::with_hasher(capacity, RandomState::new())
+    }
+}
+
+impl<K: Hash + Eq, V, S: BuildHasher> SwissHashTable<K, V, S> {
+    /// Creates a table with room for at least `capacity` buckets, rounded up to a power of two number of
+    /// groups of 16 buckets.
+    pub fn with_hasher(capacity: usize, hash_builder: S) -> Self {
+        let capacity = group_aligned_capacity(capacity);
+        SwissHashTable {
+            table: hash_table::empty_buckets(capacity),
+            ctrl: vec![[EMPTY; GROUP_WIDTH]; capacity / GROUP_WIDTH],
+            capacity,
+            size: 0,
+            hash_builder,
+            max_load_factor: DEFAULT_MAX_LOAD_FACTOR,
+            growth_factor: hash_table::DEFAULT_GROWTH_FACTOR,
+            min_load_factor: None,
+            tombstones: 0,
+        }
+    }
+
+    /// The table grows before an insertion would take it above this fraction of its capacity.
+    pub fn set_max_load_factor(&mut self, max_load_factor: f64) {
+        hash_table::assert_valid_max_load_factor(max_load_factor);
+        hash_table::assert_valid_min_load_factor(self.min_load_factor, max_load_factor);
+        self.max_load_factor = max_load_factor;
+    }
+
+    /// Makes removals shrink the table once its load factor drops below `min_load_factor`.
+    /// `None`, the default, never shrinks automatically.
+    pub fn set_min_load_factor(&mut self, min_load_factor: Option<f64>) {
+        hash_table::assert_valid_min_load_factor(min_load_factor, self.max_load_factor);
+        self.min_load_factor = min_load_factor;
+    }
+
+    /// Factor the capacity is multiplied by whenever the table grows.
+    pub fn set_growth_factor(&mut self, growth_factor: f64) {
+        hash_table::assert_valid_growth_factor(growth_factor);
+        self.growth_factor = growth_factor;
+    }
+
+    /// Number of buckets holding a tombstone left behind by a removal.
+    pub fn tombstone_count(&self) -> usize {
+        self.tombstones
+    }
+
+    /// Rebuilds the table at its current capacity, dropping every tombstone. Live entries are moved out into
+    /// a temporary `Vec` and reinserted into the same bucket and control arrays, which are never reallocated.
+    pub fn compact(&mut self) {
+        let entries: Vec<(K, V)> = self.table.iter_mut().filter_map(Option::take).collect();
+        self.ctrl.fill([EMPTY; GROUP_WIDTH]);
+        self.size = 0;
+        self.tombstones = 0;
+        for (key, value) in entries {
+            self.insert(key, value);
+        }
+    }
+
+    // Groups visited for a hash, in order. The group steps grow by one each time, and with a power of two
+    // number of groups these triangular steps visit every group exactly once.
+    fn probe_sequence(&self, hash: u64) -> impl Iterator<Item = usize> + use<K, V, S> {
+        let mask = self.ctrl.len() - 1;
+        let mut group_index = hash as usize & mask;
+        (0..self.ctrl.len()).map(move |step| {
+            group_index = (group_index + step) & mask;
+            group_index
+        })
+    }
+
+    fn set_ctrl(&mut self, index: usize, ctrl: u8) {
+        self.ctrl[index / GROUP_WIDTH][index % GROUP_WIDTH] = ctrl;
+    }
+
+    fn resize_to(&mut self, new_capacity: usize) -> bool {
+        let new_capacity = group_aligned_capacity(new_capacity);
+        if !hash_table::fits_in_capacity(self.size, new_capacity, self.max_load_factor) {
+            return false;
+        }
+        self.capacity = new_capacity;
+        let old_table = std::mem::replace(&mut self.table, hash_table::empty_buckets(self.capacity));
+        self.ctrl = vec![[EMPTY; GROUP_WIDTH]; self.capacity / GROUP_WIDTH];
+        self.size = 0;
+        self.tombstones = 0;
+        for (key, value) in old_table.into_iter().flatten() {
+            self.insert(key, value);
+        }
+        true
+    }
+
+    // Shrinks the table if removals took it below the minimum load factor.
+    fn shrink_if_underloaded(&mut self) {
+        if let Some(min_load_factor) = self.min_load_factor
+            && (self.size as f64) < self.capacity as f64 * min_load_factor
+        {
+            self.shrink_to(hash_table::shrunk_capacity(self.size, min_load_factor, self.max_load_factor));
+        }
+    }
+
+    // Capacity to resize to before inserting one more entry, if the table has to be resized. Resizing to the
+    // current capacity compacts the table instead of growing it.
+    fn capacity_for_one_more(&self) -> Option<usize> {
+        if hash_table::exceeds_max_load_factor(self.size, self.capacity, self.max_load_factor) {
+            // Resize the table because it's getting too full.
+            Some(hash_table::grown_capacity(self.capacity, self.growth_factor))
+        } else if hash_table::exceeds_max_load_factor(
+            self.size + self.tombstones,
+            self.capacity,
+            self.max_load_factor,
+        ) {
+            // Same trade-off as the linear probing table: compact if live entries only take up half of what
+            // the load factor allows, otherwise grow.
+            if hash_table::exceeds_max_load_factor(self.size, self.capacity, self.max_load_factor / 2.0) {
+                Some(hash_table::grown_capacity(self.capacity, self.growth_factor))
+            } else {
+                Some(self.capacity)
+            }
+        } else {
+            None
+        }
+    }
+
+    // Makes room for one more entry. Called before probing, so that the bucket the probe picks for a new
+    // entry is still the right one when the entry is inserted.
+    fn reserve_one(&mut self) {
+        match self.capacity_for_one_more() {
+            Some(capacity) if capacity == self.capacity => self.compact(),
+            Some(capacity) => assert!(self.resize_to(capacity)),
+            None => {}
+        }
+    }
+
+    // Probes for `key` once. Returns `Ok` with the bucket holding the key, or `Err` with the bucket a new
+    // entry for the key goes into: the first empty or deleted bucket along the probe sequence.
+    fn find_bucket_for_insert(&self, key: &K) -> Result<usize, usize> {
+        let hash = self.hash_builder.hash_one(key);
+        let mut free_index = None;
+        for group_index in self.probe_sequence(hash) {
+            let group = Group::load(&self.ctrl[group_index]);
+            if let Some(index) = self.find_in_group(group_index, &group, fragment(hash), |existing| existing == key) {
+                return Ok(index);
+            }
+            if free_index.is_none() {
+                free_index = group.match_empty_or_deleted().lowest().map(|slot| group_index * GROUP_WIDTH + slot);
+            }
+            // A group with an empty bucket was never full, so no probe for the key went past it.
+            if group.match_empty().any() {
+                break;
+            }
+        }
+        // Room for one more entry is made before probing, so some bucket along the sequence is free.
+        Err(free_index.unwrap())
+    }
+
+    fn find_index_for_key_if_exists(&self, key: &K) -> Option<usize> {
+        self.probe(self.hash_builder.hash_one(key), |existing_key| existing_key == key).0
+    }
+
+    // Follows the probe sequence for `hash` until `is_match` accepts a key whose control byte matches the hash
+    // fragment, or a group has an empty bucket. Returns the bucket of the accepted key, if any, and the number
+    // of groups probed.
+    fn probe(&self, hash: u64, is_match: impl Fn(&K) -> bool) -> (Option<usize>, usize) {
+        let mut groups = 0;
+        for group_index in self.probe_sequence(hash) {
+            groups += 1;
+            let group = Group::load(&self.ctrl[group_index]);
+            if let Some(index) = self.find_in_group(group_index, &group, fragment(hash), &is_match) {
+                return (Some(index), groups);
+            }
+            if group.match_empty().any() {
+                return (None, groups);
+            }
+        }
+        (None, groups)
+    }
+
+    // Checks the buckets of the group whose control byte matches the hash fragment.
+    fn find_in_group(
+        &self,
+        group_index: usize,
+        group: &Group,
+        fragment: u8,
+        is_match: impl Fn(&K) -> bool,
+    ) -> Option<usize> {
+        group.match_byte(fragment).map(|slot| group_index * GROUP_WIDTH + slot).find(|&index| {
+            matches!(&self.table[index], Some((existing_key, _)) if is_match(existing_key))
+        })
+    }
+
+    // Empties a bucket. A group that still has an empty bucket never stopped a probe from ending, so the
+    // bucket can go straight back to empty; only buckets of full groups need a tombstone.
+    fn erase(&mut self, index: usize) -> (K, V) {
+        let group = Group::load(&self.ctrl[index / GROUP_WIDTH]);
+        if group.match_empty().any() {
+            self.set_ctrl(index, EMPTY);
+        } else {
+            self.set_ctrl(index, DELETED);
+            self.tombstones += 1;
+        }
+        self.size -= 1;
+        self.table[index].take().unwrap()
+    }
+
+    // Checks that every control byte agrees with its bucket and the counters agree with the control bytes.
+    fn assert_control_bytes_are_consistent(&self) {
+        let mut deleted = 0;
+        for (index, entry) in self.table.iter().enumerate() {
+            let ctrl = self.ctrl[index / GROUP_WIDTH][index % GROUP_WIDTH];
+            match entry {
+                Some((key, _)) => {
+                    let expected = fragment(self.hash_builder.hash_one(key));
+                    assert!(ctrl == expected, "Bucket {} has control byte {:#04x}, not {:#04x}", index, ctrl, expected);
+                }
+                None => {
+                    assert!(ctrl == EMPTY || ctrl == DELETED, "Empty bucket {} has control byte {:#04x}", index, ctrl);
+                    deleted += usize::from(ctrl == DELETED);
+                }
+            }
+        }
+        assert!(deleted == self.tombstones, "{} deleted buckets, {} tombstones", deleted, self.tombstones);
+    }
+}
+
+impl<K: Debug, V: Debug, S> SwissHashTable<K, V, S> {
+    pub fn print(&self) {
+        println!("Capacity: {}", self.capacity);
+        println!("Size: {}", self.size);
+        println!("Tombstones: {}", self.tombstones);
+        println!("Entries (with the control byte of each bucket):");
+        for (group_index, (group, ctrl)) in self.table.chunks(GROUP_WIDTH).zip(&self.ctrl).enumerate() {
+            println!("    Group {}:", group_index);
+            for (entry, ctrl) in group.iter().zip(ctrl) {
+                match entry {
+                    Some((key, value)) => println!("\t[{:#04x}] {:?}: {:?}", ctrl, key, value),
+                    None if *ctrl == DELETED => println!("\t[{:#04x}] <TOMBSTONE>", ctrl),
+                    None => println!("\t[{:#04x}] <None>", ctrl),
+                }
+            }
+        }
+    }
+}
+
+impl<K: Hash + Eq, V, S: BuildHasher> HashTable<K, V> for SwissHashTable<K, V, S> {
+    fn len(&self) -> usize {
+        self.size
+    }
+
+    fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    fn entry(&mut self, key: K) -> Entry<'_, K, V> {
+        self.reserve_one();
+        match self.find_bucket_for_insert(&key) {
+            Ok(index) => Entry::Occupied(OccupiedEntry::from_bucket(&mut self.table[index])),
+            Err(index) => Entry::Vacant(VacantEntry::new(key, index, self)),
+        }
+    }
+
+    fn get_key_value(&self, key: &K) -> Option<(&K, &V)> {
+        let found_index = self.find_index_for_key_if_exists(key)?;
+        self.table[found_index].as_ref().map(|(key, value)| (key, value))
+    }
+
+    fn get_mut(&mut self, key: &K) -> Option<&mut V> {
+        let found_index = self.find_index_for_key_if_exists(key)?;
+        self.table[found_index].as_mut().map(|(_, value)| value)
+    }
+
+    fn get_many_mut<const N: usize>(&mut self, keys: [&K; N]) -> Option<[&mut V; N]> {
+        let mut indices = [0; N];
+        for (index, key) in indices.iter_mut().zip(keys) {
+            *index = self.find_index_for_key_if_exists(key)?;
+        }
+        hash_table::values_at_disjoint_buckets(&mut self.table, indices)
+    }
+
+    fn remove_entry(&mut self, key: &K) -> Option<(K, V)> {
+        let found_index = self.find_index_for_key_if_exists(key)?;
+        let entry = self.erase(found_index);
+        self.shrink_if_underloaded();
+        Some(entry)
+    }
+
+    fn retain<F: FnMut(&K, &mut V) -> bool>(&mut self, mut f: F) {
+        for index in 0..self.capacity {
+            if let Some((key, value)) = &mut self.table[index]
+                && !f(key, value)
+            {
+                self.erase(index);
+            }
+        }
+        self.shrink_if_underloaded();
+    }
+
+    fn clear(&mut self) {
+        self.table = hash_table::empty_buckets(self.capacity);
+        self.ctrl.fill([EMPTY; GROUP_WIDTH]);
+        self.size = 0;
+        self.tombstones = 0;
+    }
+
+    fn iter<'a>(&'a self) -> impl ExactSizeIterator<Item = (&'a K, &'a V)>
+    where
+        K: 'a,
+        V: 'a,
+    {
+        hash_table::Iter::new(&self.table, self.size)
+    }
+
+    fn iter_mut<'a>(&'a mut self) -> impl ExactSizeIterator<Item = (&'a K, &'a mut V)>
+    where
+        K: 'a,
+        V: 'a,
+    {
+        hash_table::IterMut::new(&mut self.table, self.size)
+    }
+
+    fn drain(&mut self) -> impl ExactSizeIterator<Item = (K, V)> {
+        let len = self.size;
+        self.size = 0;
+        self.tombstones = 0;
+        self.ctrl.fill([EMPTY; GROUP_WIDTH]);
+        hash_table::Drain::new(&mut self.table, len)
+    }
+
+    fn shrink_to(&mut self, min_capacity: usize) {
+        let new_capacity = min_capacity.max(hash_table::min_capacity_for(self.size, self.max_load_factor));
+        if group_aligned_capacity(new_capacity) < self.capacity {
+            assert!(self.resize_to(new_capacity));
+        }
+    }
+}
+
+impl<K: Hash + Eq, V, S: BuildHasher> InsertAtBucket<K, V> for SwissHashTable<K, V, S> {
+    fn insert_at_bucket(&mut self, index: usize, key: K, value: V) -> &mut V {
+        if self.ctrl[index / GROUP_WIDTH][index % GROUP_WIDTH] == DELETED {
+            // The bucket holds a tombstone that is being reused.
+            self.tombstones -= 1;
+        }
+        self.set_ctrl(index, fragment(self.hash_builder.hash_one(&key)));
+        self.size += 1;
+        &mut self.table[index].insert((key, value)).1
+    }
+}
+
+impl<K: Hash + Eq, V, S: BuildHasher + Clone> MigrateBuckets<K, V> for SwissHashTable<K, V, S> {
+    fn capacity_to_resize_to(&self) -> Option<usize> {
+        self.capacity_for_one_more()
+    }
+
+    fn capacity_for(&self, len: usize) -> usize {
+        hash_table::min_capacity_for(len, self.max_load_factor)
+    }
+
+    fn empty_with_capacity(&self, capacity: usize) -> Self {
+        let mut hash_table = Self::with_hasher(capacity, self.hash_builder.clone());
+        hash_table.max_load_factor = self.max_load_factor;
+        hash_table.growth_factor = self.growth_factor;
+        hash_table.min_load_factor = self.min_load_factor;
+        hash_table
+    }
+
+    fn bucket_count(&self) -> usize {
+        self.table.len()
+    }
+
+    fn bucket_of(&self, key: &K) -> Option<usize> {
+        self.find_index_for_key_if_exists(key)
+    }
+
+    fn take_bucket(&mut self, index: usize) -> impl Iterator<Item = (K, V)> {
+        let entry = self.table[index].is_some().then(|| self.erase(index));
+        entry.into_iter()
+    }
+}
+
+impl<K: Hash + Eq, V, S: BuildHasher> HashTableStats for SwissHashTable<K, V, S> {
+    fn stats(&self) -> Stats {
+        let hits = self.keys().map(|key| {
+            self.probe(self.hash_builder.hash_one(key), |existing_key| existing_key == key).1
+        });
+        // The probe sequence starts from the hash's group, and keys whose fragment happens to match cost a
+        // comparison but not a group, so misses are simulated with sample hashes.
+        let misses = hash_table::sample_hashes(self.capacity).map(|hash| self.probe(hash, |_| false).1);
+        let mut stats = Stats::new(self.size, self.capacity, hits.collect(), misses.collect());
+        stats.tombstones = self.tombstones;
+        let buckets: Vec<Option<()>> = self.ctrl.iter().flatten().map(|&ctrl| (ctrl != EMPTY).then_some(())).collect();
+        stats.longest_cluster = hash_table::longest_cluster(&buckets);
+        stats
+    }
+}
+
+impl<K, V, S> IntoIterator for SwissHashTable<K, V, S> {
+    type Item = (K, V);
+    type IntoIter = hash_table::IntoIter<Option<(K, V)>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        hash_table::IntoIter::new(self.table, self.size)
+    }
+}
+
+impl<'a, K, V, S> IntoIterator for &'a SwissHashTable<K, V, S> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = hash_table::Iter<'a, Option<(K, V)>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        hash_table::Iter::new(&self.table, self.size)
+    }
+}
+
+impl<'a, K, V, S> IntoIterator for &'a mut SwissHashTable<K, V, S> {
+    type Item = (&'a K, &'a mut V);
+    type IntoIter = hash_table::IterMut<'a, Option<(K, V)>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        hash_table::IterMut::new(&mut self.table, self.size)
+    }
+}
+
+// Checks the group matching in use against the scalar one, so the SSE2 version is checked on x86_64.
+fn check_group_matching() {
+    let mut ctrl = [EMPTY; GROUP_WIDTH];
+    for seed in 0..1000u64 {
+        for (i, byte) in ctrl.iter_mut().enumerate() {
+            *byte = match hash_table::mix64(seed * GROUP_WIDTH as u64 + i as u64) % 4 {
+                0 => EMPTY,
+                1 => DELETED,
+                // Few distinct fragments, so that several buckets of a group match.
+                _ => (hash_table::mix64(seed) % 3) as u8 * 0x2a,
+            };
+        }
+        let (group, reference) = (Group::load(&ctrl), scalar::Group::load(&ctrl));
+        for byte in [0x00, 0x2a, 0x54, 0x7f] {
+            assert!(group.match_byte(byte) == reference.match_byte(byte), "Fragment {:#04x} of {:?}", byte, ctrl);
+        }
+        assert!(group.match_empty() == reference.match_empty(), "Empty buckets of {:?}", ctrl);
+        assert!(group.match_empty_or_deleted() == reference.match_empty_or_deleted(), "Free buckets of {:?}", ctrl);
+        let expected: Vec<usize> = (0..GROUP_WIDTH).filter(|&i| ctrl[i] == EMPTY).collect();
+        assert!(group.match_empty().collect::<Vec<usize>>() == expected, "Empty buckets of {:?}", ctrl);
+    }
+}
+
+pub fn run() {
+    check_group_matching();
+
+    // Two groups of 16 buckets. The identity hasher sends odd keys to group 1 and even keys to group 0, and
+    // gives every small key the fragment 0, so fragments match and the key comparison tells keys apart.
+    let capacity: usize = 32;
+    let mut hash_table = SwissHashTable::with_hasher(capacity, BuildIdentityHasher::default());
+
+    for key in (1..32).step_by(2) {
+        hash_table.insert(key, key.to_string());
+    }
+    println!("Initial, group 1 should be full:");
+    hash_table.print();
+    assert!(hash_table.capacity == 32);
+    assert!(hash_table.size == 16);
+    assert!(hash_table.ctrl[1] == [0; GROUP_WIDTH]);
+    assert!(hash_table.ctrl[0] == [EMPTY; GROUP_WIDTH]);
+    for key in (1..32).step_by(2) {
+        assert!(hash_table.get(&key) == Some(&key.to_string()), "Wrong value for key {}", key);
+    }
+    hash_table.assert_control_bytes_are_consistent();
+
+    hash_table.insert(33, "33".to_string());
+    println!("Inserted 33, which should overflow from group 1 into the first bucket of group 0:");
+    hash_table.print();
+    assert!(hash_table.find_index_for_key_if_exists(&33) == Some(0));
+    hash_table.assert_control_bytes_are_consistent();
+
+    assert!(hash_table.remove(&5) == Some("5".to_string()));
+    println!("After deleting key 5 from the full group 1, which should leave a tombstone:");
+    hash_table.print();
+    assert!(hash_table.tombstones == 1);
+    assert!(hash_table.get(&5).is_none());
+    // The tombstone keeps the probe for 33 going past group 1.
+    assert!(hash_table.get(&33) == Some(&"33".to_string()));
+    hash_table.assert_control_bytes_are_consistent();
+    // Only 33 overflowed, so it is the only key found in the second group probed. Group 1 and the first
+    // bucket of group 0 wrap around into a single cluster.
+    let stats = hash_table.stats();
+    println!("Stats: {}", stats.to_json());
+    assert!(stats.hits.histogram == [0, 15, 1]);
+    assert!(stats.tombstones == 1 && stats.longest_cluster == GROUP_WIDTH + 1);
+
+    assert!(hash_table.remove(&33) == Some("33".to_string()));
+    println!("After deleting key 33 from group 0, which has empty buckets and needs no tombstone:");
+    hash_table.print();
+    assert!(hash_table.tombstones == 1);
+    assert!(hash_table.ctrl[0] == [EMPTY; GROUP_WIDTH]);
+    hash_table.assert_control_bytes_are_consistent();
+
+    hash_table.insert(35, "35".to_string());
+    println!("Inserted 35, which should take the tombstone 5 left behind:");
+    hash_table.print();
+    assert!(hash_table.tombstones == 0);
+    assert!(hash_table.find_index_for_key_if_exists(&35) == Some(GROUP_WIDTH + 2));
+    hash_table.assert_control_bytes_are_consistent();
+
+    for key in (0..24).step_by(2) {
+        hash_table.insert(key, key.to_string());
+    }
+    assert!(hash_table.capacity == 32);
+    hash_table.insert(24, "24".to_string());
+    println!("Inserted even keys up to 24, table should be resized to 64:");
+    hash_table.print();
+    assert!(hash_table.capacity == 64);
+    assert!(hash_table.size == 29);
+    hash_table.assert_control_bytes_are_consistent();
+
+    // Real hashes spread fragments over all 128 values.
+    let mut hash_table = SwissHashTable::with_hasher(capacity, DeterministicState::default());
+    for key in 0..1000 {
+        hash_table.insert(key, key);
+    }
+    let fragments: std::collections::HashSet<u8> = hash_table.ctrl.iter().flatten().copied().collect();
+    assert!(fragments.len() > 100, "{} distinct control bytes", fragments.len());
+    hash_table.retain(|key, _| key % 3 != 0);
+    hash_table.assert_control_bytes_are_consistent();
+    println!("All tests passed");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    hash_table::hash_table_tests!(SwissHashTable);
+
+    #[test]
+    fn compaction() {
+        hash_table::check_compaction(
+            &mut SwissHashTable::new(10),
+            SwissHashTable::tombstone_count,
+            SwissHashTable::compact,
+        );
+    }
+}